@@ -0,0 +1,369 @@
+//! Runs a ROM without a window or audio and prints the machine's state,
+//! for scripts and machines with no GPU.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use rusty_chip_core::config::{NUM_KEYS, REGISTER_COUNT};
+use rusty_chip_core::{Chip8, Chip8Error, KeyState, Movie, QuirkPreset, StopReason};
+
+const USAGE: &str = "\
+Usage: rusty-chip-headless [OPTIONS] <ROM>
+
+Runs ROM for a number of cycles or frames, then prints the registers
+and the display.
+
+Options:
+      --cycles <N>      Run N steps of one CPU cycle each; steps where
+                        the CPU waits, e.g. for the display, count too.
+                        With --movie, N of the movie's recorded steps
+      --frames <N>      Run N frames of 1/60 s
+      --clock <HZ>      CPU clock, from 1 to 2000 Hz [default: 600]
+      --quirks <PRESET> Quirk preset: vip, chip48, schip11, schip or xochip
+      --seed <SEED>     Seed of the random numbers of CXNN [default: 0]
+      --input <FILE>    Press and release keys as written in FILE
+      --movie <FILE>    Play back a movie recorded with the same ROM,
+                        until it ends unless --cycles or --frames is given
+      --output <FILE>   Write the state to FILE instead of stdout
+  -h, --help            Print this help
+
+Each line of the input file is `<frame> <key> down|up`, with the key
+in hex, e.g. `30 5 down`. Lines starting with `#` are ignored.
+
+Exits with 1 if the ROM runs into a fault.";
+
+const DEFAULT_CLOCK_HZ: u64 = 600;
+/// Same limit as the GUI's.
+const MAX_CLOCK_HZ: u64 = 2000;
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// How long to run.
+#[derive(Clone, Copy)]
+enum Limit {
+    Cycles(u64),
+    Frames(u64),
+}
+
+/// A key press or release from the input file.
+struct InputEvent {
+    frame: u64,
+    key: usize,
+    down: bool,
+}
+
+struct Options {
+    rom: PathBuf,
+    limit: Option<Limit>,
+    clock_hz: u64,
+    quirks: Option<QuirkPreset>,
+    seed: u64,
+    input: Option<PathBuf>,
+    movie: Option<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+fn check_clock(clock_hz: u64) -> Result<(), String> {
+    if !(1..=MAX_CLOCK_HZ).contains(&clock_hz) {
+        return Err(format!(
+            "Invalid clock: {} (must be from 1 to {} Hz)",
+            clock_hz, MAX_CLOCK_HZ
+        ));
+    }
+    Ok(())
+}
+
+/// Parse the arguments, without the program name.
+///
+/// Returns `None` if the help was asked for.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut rom = None;
+    let mut limit = None;
+    let mut clock_hz = DEFAULT_CLOCK_HZ;
+    let mut quirks = None;
+    let mut seed = 0;
+    let mut input = None;
+    let mut movie = None;
+    let mut output = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        let number = |value: String| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid number: {}", value))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--cycles" => limit = Some(Limit::Cycles(number(value()?)?)),
+            "--frames" => limit = Some(Limit::Frames(number(value()?)?)),
+            "--clock" => {
+                clock_hz = number(value()?)?;
+                check_clock(clock_hz)?;
+            }
+            "--quirks" => {
+                let value = value()?;
+                quirks = Some(
+                    QuirkPreset::from_id(&value)
+                        .ok_or_else(|| format!("Unknown quirk preset: {}", value))?,
+                );
+            }
+            "--seed" => seed = number(value()?)?,
+            "--input" => input = Some(PathBuf::from(value()?)),
+            "--movie" => movie = Some(PathBuf::from(value()?)),
+            "--output" => output = Some(PathBuf::from(value()?)),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ if rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let rom = rom.ok_or("Missing the ROM")?;
+    if limit.is_none() && movie.is_none() {
+        return Err("One of --cycles, --frames or --movie is needed".to_string());
+    }
+
+    Ok(Some(Options {
+        rom,
+        limit,
+        clock_hz,
+        quirks,
+        seed,
+        input,
+        movie,
+        output,
+    }))
+}
+
+/// Parse an input file, sorting the events by frame.
+fn parse_input(text: &str) -> Result<Vec<InputEvent>, String> {
+    let mut events = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = || format!("Invalid input on line {}: {}", i + 1, line);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [frame, key, action] = fields[..] else {
+            return Err(error());
+        };
+        let frame = frame.parse().map_err(|_| error())?;
+        let key = usize::from_str_radix(key, 16)
+            .ok()
+            .filter(|&key| key < NUM_KEYS)
+            .ok_or_else(error)?;
+        let down = match action {
+            "down" => true,
+            "up" => false,
+            _ => return Err(error()),
+        };
+        events.push(InputEvent { frame, key, down });
+    }
+
+    // Keep the order of the events of the same frame.
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+/// The registers and the display as text, one pixel per character.
+///
+/// Pixels are `.` when off, `#` on the first plane, `+` on the second
+/// and `*` on both.
+fn dump(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "PC: 0x{:04x}", chip8.pc());
+    let _ = writeln!(out, "I: 0x{:04x}", chip8.index_register());
+    let _ = writeln!(out, "SP: {}", chip8.sp());
+    let _ = writeln!(out, "DT: {}", chip8.delay_timer());
+    let _ = writeln!(out, "ST: {}", chip8.sound_timer());
+    for i in 0..REGISTER_COUNT {
+        let _ = writeln!(out, "V{:X}: 0x{:02x}", i, chip8.registers()[i]);
+    }
+
+    let width = chip8.display_width() as usize;
+    let height = chip8.display_height() as usize;
+    let _ = writeln!(out, "Display: {}x{}", width, height);
+    for row in chip8.framebuffer()[..width * height].chunks(width) {
+        for pixel in row {
+            out.push(match pixel.planes {
+                0 => '.',
+                1 => '#',
+                2 => '+',
+                _ => '*',
+            });
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Number of steps of `movie` that take `frames` frames.
+fn movie_steps(movie: &Movie, frames: u64) -> u64 {
+    let duration = Duration::from_secs(frames) / FRAMES_PER_SECOND as u32;
+    let mut elapsed = Duration::ZERO;
+    let mut steps = 0;
+    for step in movie.steps() {
+        if elapsed >= duration {
+            break;
+        }
+        elapsed += step.delta;
+        steps += 1;
+    }
+    steps
+}
+
+/// Run `steps` steps, pressing the keys of `events` on their frames.
+///
+/// Without a movie every step is one cycle, so it runs at most one
+/// instruction. Returns the fault the ROM ran into, if any.
+fn run_steps(chip8: &mut Chip8, steps: u64, events: &[InputEvent]) -> Option<Chip8Error> {
+    let clock_hz = chip8.clock_hz;
+    let delta = Duration::from_nanos(1_000_000_000 / clock_hz);
+
+    chip8.run();
+    let mut events = events.iter().peekable();
+    for cycle in 0..steps {
+        let frame = cycle * FRAMES_PER_SECOND / clock_hz;
+        while let Some(event) = events.next_if(|event| event.frame <= frame) {
+            chip8.input[event.key] = if event.down {
+                KeyState::Pressed
+            } else {
+                KeyState::JustReleased
+            };
+        }
+
+        match chip8.step(delta) {
+            Ok(res) if matches!(res.stop, Some(StopReason::MovieEnded)) => break,
+            Ok(_) => {}
+            Err(err) => return Some(err),
+        }
+    }
+
+    None
+}
+
+fn run(options: &Options) -> Result<bool, String> {
+    let read = |path: &PathBuf| {
+        fs::read(path).map_err(|err| format!("Couldn't read {}: {}", path.display(), err))
+    };
+
+    let mut chip8 = Chip8::new(options.clock_hz, false);
+    if let Some(preset) = options.quirks {
+        chip8.apply_preset(preset);
+    }
+    chip8.set_seed(options.seed);
+
+    let rom = read(&options.rom)?;
+    chip8
+        .insert_cartridge(&rom)
+        .map_err(|err| format!("Couldn't load {}: {}", options.rom.display(), err))?;
+
+    let events = match &options.input {
+        Some(path) => {
+            let text = String::from_utf8(read(path)?)
+                .map_err(|_| format!("{} is not a text file", path.display()))?;
+            parse_input(&text)?
+        }
+        None => Vec::new(),
+    };
+    let movie = match &options.movie {
+        Some(path) => Some(Movie::from_bytes(&read(path)?).map_err(|err| err.to_string())?),
+        None => None,
+    };
+
+    let steps = match options.limit {
+        Some(Limit::Cycles(cycles)) => cycles,
+        // Played steps take as long as when they were recorded.
+        Some(Limit::Frames(frames)) => match &movie {
+            Some(movie) => movie_steps(movie, frames),
+            None => {
+                frames
+                    .checked_mul(chip8.clock_hz)
+                    .ok_or_else(|| format!("Too many frames: {}", frames))?
+                    / FRAMES_PER_SECOND
+            }
+        },
+        None => u64::MAX,
+    };
+    if let Some(movie) = movie {
+        chip8.play_movie(movie).map_err(|err| err.to_string())?;
+    }
+    // A movie brings its own clock.
+    check_clock(chip8.clock_hz)?;
+
+    let fault = run_steps(&mut chip8, steps, &events);
+
+    let mut out = dump(&chip8);
+    if let Some(err) = fault {
+        let _ = writeln!(out, "Fault: {}", err);
+    }
+    match &options.output {
+        Some(path) => fs::write(path, out)
+            .map_err(|err| format!("Couldn't write {}: {}", path.display(), err))?,
+        None => print!("{}", out),
+    }
+
+    Ok(fault.is_none())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movie_frames_follow_the_recorded_time() {
+        // Set DT to 255 and loop.
+        let rom = [0x60, 0xFF, 0xF0, 0x15, 0x12, 0x04];
+
+        // The GUI steps every 1/2000 s, whatever the clock is.
+        let mut chip8 = Chip8::new(600, false);
+        chip8.insert_cartridge(&rom).unwrap();
+        assert!(chip8.start_recording());
+        chip8.run();
+        for _ in 0..4000 {
+            chip8.step(Duration::from_micros(500)).unwrap();
+        }
+        let movie = chip8.stop_recording().unwrap();
+
+        let steps = movie_steps(&movie, 60);
+        assert_eq!(steps, 2000);
+
+        chip8.play_movie(movie).unwrap();
+        assert!(run_steps(&mut chip8, steps, &[]).is_none());
+        let frames = 255 - chip8.delay_timer();
+        assert!(frames.abs_diff(60) <= 1, "{} frames", frames);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::condition::Condition;
+
+/// A breakpoint on an instruction's address.
+///
+/// Disabled breakpoints are kept, so they can be enabled again later.
+/// A breakpoint with a `condition` only stops the emulation if the
+/// condition holds right before the instruction is executed.
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub enabled: bool,
+    pub condition: Option<Condition>,
+}
+
+impl Default for Breakpoint {
+    fn default() -> Self {
+        Breakpoint {
+            enabled: true,
+            condition: None,
+        }
+    }
+}
+
+/// Kind of memory access done by an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A watchpoint on a range of RAM addresses.
+///
+/// Only data accesses by instructions are watched, i.e. sprite reads,
+/// FX33, FX55, FX65 and XO-CHIP's 5XY2 and 5XY3. Fetching instructions
+/// doesn't trigger watchpoints.
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<usize>,
+    pub read: bool,
+    pub write: bool,
+    pub enabled: bool,
+}
+
+impl Watchpoint {
+    pub fn new(range: RangeInclusive<usize>, read: bool, write: bool) -> Watchpoint {
+        Watchpoint {
+            range,
+            read,
+            write,
+            enabled: true,
+        }
+    }
+
+    /// Returns true if the `access` at `addr` should pause the emulation.
+    pub fn triggers(&self, addr: usize, access: Access) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+
+        self.enabled && watched && self.range.contains(&addr)
+    }
+}
+
+/// A memory access that triggered a watchpoint.
+///
+/// `pc` is the address of the instruction doing the access.
+/// For reads `old` and `new` are both the value read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub addr: usize,
+    pub access: Access,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// Why the emulation paused itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// Reached the breakpoint at the given address.
+    Breakpoint(u16),
+    /// An instruction accessed memory under a watchpoint. The
+    /// instruction is executed before pausing.
+    Watchpoint(WatchHit),
+    /// Finished a step over, step out or run to cursor, at the given address.
+    Reached(u16),
+    /// The movie being played back ended.
+    MovieEnded,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint(addr) => write!(f, "Breakpoint at 0x{:04x}", addr),
+            StopReason::Reached(addr) => write!(f, "Reached 0x{:04x}", addr),
+            StopReason::MovieEnded => write!(f, "Movie ended"),
+            StopReason::Watchpoint(hit) => match hit.access {
+                Access::Read => write!(
+                    f,
+                    "Read of 0x{:04x} by PC 0x{:04x}: 0x{:02x}",
+                    hit.addr, hit.pc, hit.old
+                ),
+                Access::Write => write!(
+                    f,
+                    "Write to 0x{:04x} by PC 0x{:04x}: 0x{:02x} -> 0x{:02x}",
+                    hit.addr, hit.pc, hit.old, hit.new
+                ),
+            },
+        }
+    }
+}
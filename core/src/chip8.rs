@@ -0,0 +1,1736 @@
+use rand::random;
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use crate::breakpoint::{Access, Breakpoint, StopReason, WatchHit, Watchpoint};
+use crate::condition::Condition;
+use crate::config::{
+    AUDIO_PATTERN_SIZE, BIG_FONT, BIG_FONT_RANGE, DEFAULT_PITCH, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+    FONT, FONT_RANGE, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH, NUM_KEYS, PLANE_COUNT, RAM_SIZE,
+    REGISTER_COUNT, RPL_FLAG_COUNT, SCHIP_RPL_FLAG_COUNT, STACK_SIZE, START_PC, UNDO_HISTORY_SIZE,
+    XO_CHIP_RAM_SIZE,
+};
+use crate::fault::{Chip8Error, FaultKind};
+use crate::history::{CpuState, UndoEntry};
+use crate::instruction::{decode, decode_at, Instruction};
+use crate::movie::{Movie, MovieCursor, MovieError, MovieStep};
+use crate::quirks::{QuirkPreset, Quirks};
+use crate::rng::Rng;
+use crate::state::{key_state, StateError, StateReader, StateWriter};
+use crate::timer::Timer;
+
+/// CHIP-8 display pixel's representation.
+///
+/// `planes` has a bit for each of XO-CHIP's bitplanes that is set
+/// for this pixel, so it selects one of 4 colours.
+/// `fade` is the intensity of the trace left after the pixel is erased.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayPixel {
+    pub planes: u8,
+    pub fade: u8,
+}
+
+/// The framebuffer is big enough for the high resolution mode.
+/// In low resolution mode only the first `DISPLAY_WIDTH * DISPLAY_HEIGHT`
+/// pixels are used.
+const NUM_PIXELS: usize = (HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT) as usize;
+
+/// CHIP-8 state. If paused the user can step through
+/// the instructions one by one.
+#[derive(PartialEq)]
+enum ConsoleState {
+    Paused,
+    Running,
+}
+
+/// Where to pause when running with a goal,
+/// used by step over, step out and run to cursor.
+#[derive(Clone, Copy)]
+enum RunGoal {
+    /// Pause after the next instruction.
+    Step,
+    /// Pause at `addr`. If `sp` is set, only in that stack frame.
+    Address { addr: u16, sp: Option<usize> },
+    /// Pause once the frame at `sp` returns.
+    Return { sp: usize },
+}
+
+/// The movie being recorded or played back.
+enum MovieMode {
+    Recording(Movie),
+    Playing { movie: Movie, cursor: MovieCursor },
+}
+
+/// CHIP-8 key state.
+///
+/// There is a peculiarity in the instruction FX0A(Get key).
+/// It busy waits until a key is pressed, but only detects it
+/// after a key was pressed and then released.
+/// Thus we need the JustReleased state.
+#[derive(PartialEq, Clone, Copy)]
+pub enum KeyState {
+    Released = 0,
+    Pressed = 1,
+    JustReleased = 2,
+}
+
+/// CHIP-8's state.
+///
+/// Nothing fancy. Most of it you find on every CHIP-8
+/// emulator tutorial. Added stuff is for user-friendliness.
+pub struct Chip8 {
+    ram: Vec<u8>,
+    stack: [u16; STACK_SIZE],
+    framebuffer: [DisplayPixel; NUM_PIXELS],
+    pc: u16,
+    index_register: u16,
+    stack_ptr: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    registers: [u8; 16],
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+    hires: bool,
+    planes: u8,
+    xo_chip: bool,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    audio_pattern_loaded: bool,
+    pitch: u8,
+    seed: u64,
+    rng: Rng,
+
+    timer_clock: Timer,
+    timer_60hz: Timer,
+
+    state: ConsoleState,
+    fault: Option<Chip8Error>,
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Option<WatchHit>,
+    stop: Option<StopReason>,
+    resuming: bool,
+    goal: Option<RunGoal>,
+    history: VecDeque<UndoEntry>,
+    undo: Option<UndoEntry>,
+    movie: Option<MovieMode>,
+    vblank_wait: bool,
+    rom: Vec<u8>,
+    rom_size: usize,
+    rom_id: u64,
+    rpl_flags_dirty: bool,
+    reset: bool,
+    debug: bool,
+    trace: bool,
+    reduce_flicker: bool,
+
+    pub input: [KeyState; NUM_KEYS],
+    pub clock_hz: u64,
+    pub quirks: Quirks,
+}
+
+impl Chip8 {
+    /// Read a byte from RAM.
+    fn read(&mut self, addr: usize) -> Result<u8, FaultKind> {
+        let value = *self.ram.get(addr).ok_or(FaultKind::OutOfBounds(addr))?;
+        self.watch(addr, Access::Read, value, value);
+        Ok(value)
+    }
+
+    /// Write a byte to RAM.
+    fn write(&mut self, addr: usize, value: u8) -> Result<(), FaultKind> {
+        let byte = self.ram.get_mut(addr).ok_or(FaultKind::OutOfBounds(addr))?;
+        let old = *byte;
+        *byte = value;
+        if let Some(undo) = &mut self.undo {
+            undo.ram.push((addr, old));
+        }
+        self.watch(addr, Access::Write, old, value);
+        Ok(())
+    }
+
+    /// Change a pixel of the framebuffer.
+    fn set_pixel(&mut self, idx: usize, pixel: DisplayPixel) {
+        let old = std::mem::replace(&mut self.framebuffer[idx], pixel);
+        if old == pixel {
+            return;
+        }
+        if let Some(undo) = &mut self.undo {
+            undo.pixels.push((idx, old));
+        }
+    }
+
+    fn cpu_state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            index_register: self.index_register,
+            stack: self.stack,
+            stack_ptr: self.stack_ptr,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            registers: self.registers,
+            rpl_flags: self.rpl_flags,
+            hires: self.hires,
+            planes: self.planes,
+            audio_pattern: self.audio_pattern,
+            audio_pattern_loaded: self.audio_pattern_loaded,
+            pitch: self.pitch,
+            vblank_wait: self.vblank_wait,
+            input: self.input,
+            rng: self.rng,
+        }
+    }
+
+    fn set_cpu_state(&mut self, cpu: CpuState) {
+        self.pc = cpu.pc;
+        self.index_register = cpu.index_register;
+        self.stack = cpu.stack;
+        self.stack_ptr = cpu.stack_ptr;
+        self.delay_timer = cpu.delay_timer;
+        self.sound_timer = cpu.sound_timer;
+        self.registers = cpu.registers;
+        self.rpl_flags = cpu.rpl_flags;
+        self.hires = cpu.hires;
+        self.planes = cpu.planes;
+        self.audio_pattern = cpu.audio_pattern;
+        self.audio_pattern_loaded = cpu.audio_pattern_loaded;
+        self.pitch = cpu.pitch;
+        self.vblank_wait = cpu.vblank_wait;
+        self.input = cpu.input;
+        self.rng = cpu.rng;
+    }
+
+    /// Add the changes of the instruction just executed to the history.
+    fn push_undo(&mut self) {
+        let Some(undo) = self.undo.take() else {
+            return;
+        };
+
+        if self.history.len() == UNDO_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(undo);
+    }
+
+    /// Remember the first access of the current instruction
+    /// that triggers a watchpoint.
+    fn watch(&mut self, addr: usize, access: Access, old: u8, new: u8) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+
+        if self.watchpoints.iter().any(|w| w.triggers(addr, access)) {
+            self.watch_hit = Some(WatchHit {
+                // Filled in by `step`, which knows the instruction's address.
+                pc: 0,
+                addr,
+                access,
+                old,
+                new,
+            });
+        }
+    }
+
+    /// Read the next instruction and increase the program counter.
+    fn fetch(&mut self) -> Result<u16, FaultKind> {
+        let pc = self.pc as usize;
+        let bytes = self.ram.get(pc..pc + 2).ok_or(FaultKind::OutOfBounds(pc))?;
+        self.pc = self.pc.wrapping_add(2);
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skip the next instruction.
+    ///
+    /// XO-CHIP's F000 NNNN is 4 bytes long, so it must be skipped whole.
+    fn skip(&mut self) {
+        let len = match decode_at(&self.ram, self.pc as usize) {
+            Some((Instruction::LongLoadIndex, len)) if self.xo_chip => len,
+            _ => 2,
+        };
+        self.pc = self.pc.wrapping_add(len as u16);
+    }
+
+    /// Draw the sprite specified by the instruction.
+    ///
+    /// # Returns true if the display should be updated. False, otherwise.
+    ///
+    /// DXY0 draws a SUPER-CHIP 16x16 sprite, stored as two bytes per row.
+    ///
+    /// The sprite is drawn on each plane selected by FN01. When both
+    /// planes are selected the sprite data for the second plane follows
+    /// the data for the first one.
+    ///
+    /// The sprite's position always wraps around the display. Parts of the
+    /// sprite going over the edges are clipped, unless the wrapping quirk
+    /// is enabled in which case they are drawn on the opposite side.
+    ///
+    /// In high resolution VF holds the number of sprite rows that collided
+    /// or were cut off at the bottom of the display, as SUPER-CHIP does.
+    /// In low resolution VF is just set to 1 on any collision.
+    ///
+    /// If `self.reduce_flicker` is true it checks if we are just erasing a sprite
+    /// (i.e all the pixels that are changed were 1 to 0 flips) and if that is the case
+    /// we don't update the display.
+    /// `self.reduce_flicker` being false means we always want to update the display after
+    /// the instruction was called.
+    fn display(&mut self, x: u8, y: u8, n: u8) -> Result<bool, FaultKind> {
+        let width = self.display_width() as usize;
+        let height = self.display_height() as usize;
+
+        // Assume display sizes are powers of 2.
+        // Eqiv. to (X % width)
+        let x = self.registers[x as usize] as usize & (width - 1);
+        let y = self.registers[y as usize] as usize & (height - 1);
+        let (rows, cols) = if n == 0 { (16, 16) } else { (n as usize, 8) };
+        let bytes_per_row = cols / 8;
+        let wrap = self.quirks.wrapping;
+        let mut collided_rows = [false; 16];
+        let mut drawn = false;
+        let mut addr = self.index_register as usize;
+        for plane in 0..PLANE_COUNT {
+            let mask = 1 << plane;
+            if self.planes & mask == 0 {
+                continue;
+            }
+
+            for (i, collided) in collided_rows.iter_mut().enumerate().take(rows) {
+                let py = y + i;
+                if py >= height && !wrap {
+                    break;
+                }
+                let py = py % height;
+
+                let row_addr = addr + i * bytes_per_row;
+                let mut row = (self.read(row_addr)? as u16) << 8;
+                if bytes_per_row == 2 {
+                    row |= self.read(row_addr + 1)? as u16;
+                }
+
+                for j in 0..cols {
+                    if (row & (0x8000 >> j)) == 0 {
+                        continue;
+                    }
+
+                    let px = x + j;
+                    if px >= width && !wrap {
+                        break;
+                    }
+                    let px = px % width;
+
+                    let idx = py * width + px;
+                    let mut pixel = self.framebuffer[idx];
+                    if pixel.planes & mask != 0 {
+                        pixel.planes &= !mask;
+                        if pixel.planes == 0 && self.trace {
+                            pixel.fade = 128;
+                        }
+                        *collided = true;
+                    } else {
+                        pixel.planes |= mask;
+                        pixel.fade = 0;
+                        drawn = true;
+                    }
+                    self.set_pixel(idx, pixel);
+                }
+            }
+
+            addr += rows * bytes_per_row;
+        }
+
+        let mut collisions = collided_rows.iter().filter(|&&collided| collided).count();
+        if self.hires && !wrap && y + rows > height {
+            collisions += y + rows - height;
+        }
+
+        self.registers[0xF] = if self.hires {
+            collisions as u8
+        } else {
+            cmp::min(collisions, 1) as u8
+        };
+
+        Ok(!self.reduce_flicker || drawn)
+    }
+
+    /// Clear the planes selected by FN01.
+    fn clear(&mut self) {
+        let mask = self.planes;
+        for idx in 0..NUM_PIXELS {
+            let mut pixel = self.framebuffer[idx];
+            pixel.planes &= !mask;
+            if pixel.planes == 0 {
+                pixel.fade = 0;
+            }
+            self.set_pixel(idx, pixel);
+        }
+    }
+
+    /// Switch between low (64x32) and high (128x64) resolution.
+    ///
+    /// The display is cleared on every switch as the old contents
+    /// make no sense in the new resolution.
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        for idx in 0..NUM_PIXELS {
+            self.set_pixel(idx, DisplayPixel::default());
+        }
+    }
+
+    /// Scroll the planes selected by FN01 by `dx` columns and `dy` rows.
+    ///
+    /// Positive values scroll right/down. Pixels scrolled in are blank.
+    ///
+    /// The original SUPER-CHIP 1.0/1.1 always scrolls by high resolution
+    /// pixels, so in low resolution the picture only moves by half
+    /// the amount. Modern SUPER-CHIP scrolls by the current pixel size.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (dx, dy) = if self.quirks.legacy_scroll && !self.hires {
+            (dx / 2, dy / 2)
+        } else {
+            (dx, dy)
+        };
+
+        let mask = self.planes;
+        let width = self.display_width() as isize;
+        let height = self.display_height() as isize;
+        let old = self.framebuffer;
+        for y in 0..height {
+            for x in 0..width {
+                let src_x = x - dx;
+                let src_y = y - dy;
+                let inside = src_x >= 0 && src_x < width && src_y >= 0 && src_y < height;
+
+                let src = if inside {
+                    old[(src_y * width + src_x) as usize]
+                } else {
+                    DisplayPixel::default()
+                };
+                let idx = (y * width + x) as usize;
+                let mut pixel = self.framebuffer[idx];
+                pixel.planes = (pixel.planes & !mask) | (src.planes & mask);
+                pixel.fade = src.fade;
+                self.set_pixel(idx, pixel);
+            }
+        }
+    }
+
+    /// Execute an instruction.
+    ///
+    /// # Returns true if the display should be updated, or the fault
+    /// the instruction ran into.
+    fn execute(&mut self, instr: u16) -> Result<bool, FaultKind> {
+        let instruction = decode(instr);
+
+        if self.debug {
+            println!(
+                "Execute: 0x{:04x} {}",
+                instr,
+                instruction.mnemonic(&self.quirks)
+            );
+        }
+
+        let mut drawn = false;
+        match instruction {
+            Instruction::Clear => {
+                self.clear();
+            }
+            Instruction::ScrollDown(n) => {
+                self.scroll(0, n as isize);
+                drawn = true;
+            }
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+                drawn = true;
+            }
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+                drawn = true;
+            }
+            Instruction::Lores => {
+                self.set_hires(false);
+            }
+            Instruction::Hires => {
+                self.set_hires(true);
+            }
+            Instruction::Return => {
+                if self.stack_ptr == 0 {
+                    return Err(FaultKind::StackUnderflow);
+                }
+                self.stack_ptr -= 1;
+                self.pc = self.stack[self.stack_ptr];
+                self.stack[self.stack_ptr] = 0;
+            }
+            Instruction::Jump(addr) => {
+                self.pc = addr;
+            }
+            Instruction::Call(addr) => {
+                if self.stack_ptr == STACK_SIZE {
+                    return Err(FaultKind::StackOverflow);
+                }
+                self.stack[self.stack_ptr] = self.pc;
+                self.stack_ptr += 1;
+                self.pc = addr;
+            }
+            Instruction::SkipEqByte { x, byte } => {
+                if self.registers[x as usize] == byte {
+                    self.skip();
+                }
+            }
+            Instruction::SkipNeByte { x, byte } => {
+                if self.registers[x as usize] != byte {
+                    self.skip();
+                }
+            }
+            Instruction::SkipEqReg { x, y } => {
+                if self.registers[x as usize] == self.registers[y as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::SaveRange { x, y } if self.xo_chip => {
+                // Save VX..VY to memory starting at I. I is not changed.
+                let base = self.index_register as usize;
+                for (offset, reg) in register_range(x, y).enumerate() {
+                    self.write(base + offset, self.registers[reg])?;
+                }
+            }
+            Instruction::LoadRange { x, y } if self.xo_chip => {
+                // Load VX..VY from memory starting at I. I is not changed.
+                let base = self.index_register as usize;
+                for (offset, reg) in register_range(x, y).enumerate() {
+                    self.registers[reg] = self.read(base + offset)?;
+                }
+            }
+            Instruction::LoadByte { x, byte } => {
+                self.registers[x as usize] = byte;
+            }
+            Instruction::AddByte { x, byte } => {
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(byte);
+            }
+            Instruction::Move { x, y } => {
+                self.registers[x as usize] = self.registers[y as usize];
+            }
+            Instruction::Or { x, y } => {
+                self.registers[x as usize] |= self.registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
+                }
+            }
+            Instruction::And { x, y } => {
+                self.registers[x as usize] &= self.registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
+                }
+            }
+            Instruction::Xor { x, y } => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
+                }
+            }
+            Instruction::Add { x, y } => {
+                let vx = self.registers[x as usize] as u16;
+                let vy = self.registers[y as usize] as u16;
+                let res = vx + vy;
+                self.registers[x as usize] = res as u8;
+                self.registers[0xF] = if res > 255 { 1 } else { 0 }
+            }
+            Instruction::Sub { x, y } => {
+                let vx = self.registers[x as usize] as i16;
+                let vy = self.registers[y as usize] as i16;
+                self.registers[x as usize] = (vx - vy) as u8;
+                self.registers[0xF] = if vx > vy { 1 } else { 0 }
+            }
+            Instruction::ShiftRight { x, y } => {
+                if !self.quirks.shifting {
+                    self.registers[x as usize] = self.registers[y as usize];
+                }
+                let vf = self.registers[x as usize] & 0x01;
+                self.registers[x as usize] >>= 1;
+                self.registers[0xF] = vf;
+            }
+            Instruction::SubN { x, y } => {
+                let vx = self.registers[x as usize] as i16;
+                let vy = self.registers[y as usize] as i16;
+                self.registers[x as usize] = (vy - vx) as u8;
+                self.registers[0xF] = if vy > vx { 1 } else { 0 }
+            }
+            Instruction::ShiftLeft { x, y } => {
+                if !self.quirks.shifting {
+                    self.registers[x as usize] = self.registers[y as usize];
+                }
+                let vf = (self.registers[x as usize] & 0x80) >> 7;
+                self.registers[x as usize] <<= 1;
+                self.registers[0xF] = vf;
+            }
+            Instruction::SkipNeReg { x, y } => {
+                if self.registers[x as usize] != self.registers[y as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::LoadIndex(addr) => {
+                self.index_register = addr;
+            }
+            Instruction::JumpOffset { x, addr } => {
+                let reg = if self.quirks.jumping { x } else { 0 };
+                self.pc = self.registers[reg as usize] as u16 + addr;
+            }
+            Instruction::Random { x, byte } => {
+                self.registers[x as usize] = self.rng.next_u8() & byte;
+            }
+            Instruction::Draw { x, y, n } => {
+                drawn = self.display(x, y, n)?;
+            }
+            Instruction::SkipKey(x) => {
+                let key = (self.registers[x as usize] & 0xF) as usize;
+                if self.input[key] == KeyState::Pressed {
+                    self.skip();
+                }
+            }
+            Instruction::SkipNotKey(x) => {
+                let key = (self.registers[x as usize] & 0xF) as usize;
+                if self.input[key] != KeyState::Pressed {
+                    self.skip();
+                }
+            }
+            Instruction::LongLoadIndex if self.xo_chip => {
+                // Long index load. The address is the next 2 bytes.
+                self.index_register = self.fetch()?;
+            }
+            Instruction::Plane(n) if self.xo_chip => {
+                // Select the drawing planes
+                self.planes = n;
+            }
+            Instruction::Audio if self.xo_chip => {
+                // Load the 1-bit audio pattern from memory at I
+                let base = self.index_register as usize;
+                let pattern = self
+                    .ram
+                    .get(base..base + AUDIO_PATTERN_SIZE)
+                    .ok_or(FaultKind::OutOfBounds(base))?;
+                self.audio_pattern.copy_from_slice(pattern);
+                self.audio_pattern_loaded = true;
+            }
+            Instruction::Pitch(x) if self.xo_chip => {
+                // Set the audio pattern's playback pitch
+                self.pitch = self.registers[x as usize];
+            }
+            Instruction::LoadDelay(x) => {
+                self.registers[x as usize] = self.delay_timer;
+            }
+            Instruction::SetDelay(x) => {
+                self.delay_timer = self.registers[x as usize];
+            }
+            Instruction::SetSound(x) => {
+                self.sound_timer = self.registers[x as usize];
+            }
+            Instruction::AddIndex(x) => {
+                let res = self.index_register as usize + self.registers[x as usize] as usize;
+                self.index_register = res as u16;
+                // "overflow" outside of addressing range
+                if res >= self.ram.len() {
+                    self.registers[0xF] = 1;
+                }
+            }
+            Instruction::WaitKey(x) => {
+                // Get Key
+                let mut found = false;
+                for i in 0..16 {
+                    if self.input[i] == KeyState::JustReleased {
+                        self.input[i] = KeyState::Released;
+                        self.registers[x as usize] = i as u8;
+                        found = true;
+                    }
+                }
+                if !found {
+                    self.pc = self.pc.wrapping_sub(2);
+                }
+            }
+            Instruction::Font(x) => {
+                // Font character
+                let char = (self.registers[x as usize] & 0xF) as usize;
+                // Each character sprite is represented by 5 bytes.
+                self.index_register = (FONT_RANGE.start + 5 * char) as u16;
+            }
+            Instruction::BigFont(x) => {
+                // Big font character
+                let char = (self.registers[x as usize] & 0xF) as usize;
+                // Each big character sprite is represented by 10 bytes.
+                self.index_register = (BIG_FONT_RANGE.start + 10 * char) as u16;
+            }
+            Instruction::Bcd(x) => {
+                // Binary-coded decimal. Always 3 digits, hundreds first.
+                let num = self.registers[x as usize];
+                let base = self.index_register as usize;
+                self.write(base, num / 100)?;
+                self.write(base + 1, num / 10 % 10)?;
+                self.write(base + 2, num % 10)?;
+            }
+            Instruction::SaveFlags(x) if self.rpl_flag_allowed(x) => {
+                // Save V0..VX to the RPL user flags
+                let count = x as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.registers[..count]);
+                self.rpl_flags_dirty = true;
+            }
+            Instruction::LoadFlags(x) if self.rpl_flag_allowed(x) => {
+                // Load V0..VX from the RPL user flags
+                let count = x as usize + 1;
+                self.registers[..count].copy_from_slice(&self.rpl_flags[..count]);
+            }
+            Instruction::Store(x) => {
+                let base = self.index_register as usize;
+                for j in 0..=x as usize {
+                    self.write(base + j, self.registers[j])?;
+                }
+                if self.quirks.memory_increment {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
+                }
+            }
+            Instruction::Load(x) => {
+                let base = self.index_register as usize;
+                for j in 0..=x as usize {
+                    self.registers[j] = self.read(base + j)?;
+                }
+                if self.quirks.memory_increment {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
+                }
+            }
+            // Unknown opcodes and XO-CHIP instructions while it is disabled.
+            _ => {
+                return Err(FaultKind::UnknownOpcode);
+            }
+        }
+
+        // Clear out the keyboard state if the 0xFx0A instruction was
+        // not called. This will prevent it from catching old input.
+        for i in 0..NUM_KEYS {
+            if self.input[i] == KeyState::JustReleased {
+                self.input[i] = KeyState::Released;
+            }
+        }
+
+        Ok(drawn)
+    }
+}
+
+const SECOND_IN_NS: u64 = 1000000000;
+
+/// Registers VX..VY used by XO-CHIP's 5XY2 and 5XY3.
+///
+/// The range goes backwards if X > Y.
+fn register_range(x: u8, y: u8) -> impl Iterator<Item = usize> {
+    let (x, y) = (x as usize, y as usize);
+    let count = x.abs_diff(y) + 1;
+    (0..count).map(move |i| if x <= y { x + i } else { x - i })
+}
+
+/// Largest ROM that fits in RAM after `START_PC`.
+fn max_rom_size(xo_chip: bool) -> usize {
+    let ram_size = if xo_chip { XO_CHIP_RAM_SIZE } else { RAM_SIZE };
+    ram_size - START_PC
+}
+
+/// FNV-1a hash of the ROM's data.
+///
+/// Unlike `DefaultHasher` it is stable between runs and rust versions,
+/// so it can be used to name files on disk.
+fn rom_id(data: &[u8]) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    data.iter().fold(FNV_OFFSET, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Result of calling `Chip8::step()`
+///
+/// `drawn` means we should update the screen
+/// `beep` means we should play the beep sound
+/// `stop` is set if the emulation paused itself, e.g. on a breakpoint
+pub struct StepResult {
+    pub drawn: bool, // weather or not we executed a draw instruction
+    pub beep: bool,  // weather or not sound_timer > 0
+    pub stop: Option<StopReason>,
+}
+
+impl Chip8 {
+    pub fn new(clock_hz: u64, debug: bool) -> Chip8 {
+        let seed = random();
+        let mut res = Chip8 {
+            ram: vec![0; RAM_SIZE],
+            stack: [0; STACK_SIZE],
+            framebuffer: [DisplayPixel::default(); NUM_PIXELS],
+            pc: START_PC as u16,
+            index_register: 0,
+            stack_ptr: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            registers: [0; REGISTER_COUNT],
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            hires: false,
+            planes: 1,
+            xo_chip: false,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            audio_pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            seed,
+            rng: Rng::new(seed),
+            clock_hz,
+            timer_clock: Timer::new(Duration::from_nanos(SECOND_IN_NS / clock_hz)),
+            timer_60hz: Timer::new(Duration::from_nanos(SECOND_IN_NS / 60)),
+            quirks: Quirks::default(),
+            input: [KeyState::Released; NUM_KEYS],
+            rom: Vec::new(),
+            rom_size: 0,
+            rom_id: 0,
+            rpl_flags_dirty: false,
+            reset: true,
+            debug,
+            trace: false,
+            reduce_flicker: false,
+
+            state: ConsoleState::Paused,
+            fault: None,
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            stop: None,
+            resuming: false,
+            goal: None,
+            history: VecDeque::new(),
+            undo: None,
+            movie: None,
+            vblank_wait: false,
+            //#[cfg(not(debug_assertions))]
+        };
+
+        // Copy font into memory 050–09F
+        res.ram[FONT_RANGE].copy_from_slice(&FONT);
+        // Copy big font into memory 0A0–13F
+        res.ram[BIG_FONT_RANGE].copy_from_slice(&BIG_FONT);
+
+        res
+    }
+
+    /// Load a ROM into CHIP-8's RAM.
+    ///
+    /// Fails with `FaultKind::RomTooLarge`, leaving the machine untouched,
+    /// if the ROM doesn't fit in the RAM of the current mode.
+    pub fn insert_cartridge(&mut self, data: &[u8]) -> Result<(), FaultKind> {
+        if data.len() > max_rom_size(self.xo_chip) {
+            return Err(FaultKind::RomTooLarge(data.len()));
+        }
+
+        self.reset();
+
+        // Copy program data into memory
+        self.ram[START_PC..(START_PC + data.len())].copy_from_slice(data);
+
+        self.rom = data.to_vec();
+        self.rom_size = data.len();
+        self.rom_id = rom_id(data);
+        Ok(())
+    }
+
+    /// Reset all the state. A new ROM should be loaded.
+    ///
+    /// The compatibility options chosen by the user, the RNG seed,
+    /// the breakpoints and the watchpoints are kept. A movie being
+    /// recorded or played back is stopped.
+    pub fn reset(&mut self) {
+        let quirks = self.quirks;
+        let xo_chip = self.xo_chip;
+        let seed = self.seed;
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        let watchpoints = std::mem::take(&mut self.watchpoints);
+
+        *self = Chip8::new(self.clock_hz, self.debug);
+        self.quirks = quirks;
+        self.breakpoints = breakpoints;
+        self.watchpoints = watchpoints;
+        self.set_xo_chip(xo_chip);
+        self.set_seed(seed);
+        self.reset = true;
+    }
+
+    /// SUPER-CHIP only has flags for V0..V7, XO-CHIP for all registers.
+    fn rpl_flag_allowed(&self, x: u8) -> bool {
+        self.xo_chip || (x as usize) < SCHIP_RPL_FLAG_COUNT
+    }
+
+    /// Reset the state and load the current ROM again,
+    /// like switching the machine off and on.
+    ///
+    /// Fails if the ROM no longer fits, e.g. after XO-CHIP was disabled.
+    pub fn restart(&mut self) -> Result<(), FaultKind> {
+        let rom = std::mem::take(&mut self.rom);
+        let res = self.insert_cartridge(&rom);
+        if res.is_err() {
+            self.rom = rom;
+        }
+        res
+    }
+
+    /// Serialize the whole machine state.
+    ///
+    /// The debugger's breakpoints and the display settings
+    /// are not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+
+        w.u32(self.ram.len() as u32);
+        w.bytes(&self.ram);
+        for addr in self.stack {
+            w.u16(addr);
+        }
+        w.u8(self.stack_ptr as u8);
+        for pixel in self.framebuffer {
+            w.u8(pixel.planes);
+            w.u8(pixel.fade);
+        }
+        w.u16(self.pc);
+        w.u16(self.index_register);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.bytes(&self.registers);
+        w.bytes(&self.rpl_flags);
+        w.bool(self.hires);
+        w.u8(self.planes);
+        w.bool(self.xo_chip);
+        w.bytes(&self.audio_pattern);
+        w.bool(self.audio_pattern_loaded);
+        w.u8(self.pitch);
+        w.bool(self.vblank_wait);
+        for key in self.input {
+            w.u8(key as u8);
+        }
+        w.u64(self.clock_hz);
+
+        w.quirks(&self.quirks);
+
+        w.u32(self.rom_size as u32);
+        w.u64(self.rom_id);
+
+        // Version 2
+        w.u64(self.seed);
+        w.u64(self.rng.state());
+
+        w.finish()
+    }
+
+    /// Restore a state written by `save_state`.
+    ///
+    /// Nothing is changed if the state is invalid. The emulation keeps
+    /// running or stays paused, as it was before loading.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(data)?;
+        let mut res = Chip8::new(self.clock_hz, self.debug);
+
+        let ram_size = r.u32()? as usize;
+        if ram_size != RAM_SIZE && ram_size != XO_CHIP_RAM_SIZE {
+            return Err(StateError::Invalid("RAM size"));
+        }
+        res.ram = r.bytes(ram_size)?.to_vec();
+        for addr in res.stack.iter_mut() {
+            *addr = r.u16()?;
+        }
+        res.stack_ptr = r.u8()? as usize;
+        if res.stack_ptr > STACK_SIZE {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        for pixel in res.framebuffer.iter_mut() {
+            pixel.planes = r.u8()?;
+            if pixel.planes >= 1 << PLANE_COUNT {
+                return Err(StateError::Invalid("pixel"));
+            }
+            pixel.fade = r.u8()?;
+        }
+        res.pc = r.u16()?;
+        res.index_register = r.u16()?;
+        res.delay_timer = r.u8()?;
+        res.sound_timer = r.u8()?;
+        res.registers.copy_from_slice(r.bytes(REGISTER_COUNT)?);
+        res.rpl_flags.copy_from_slice(r.bytes(RPL_FLAG_COUNT)?);
+        res.hires = r.bool()?;
+        res.planes = r.u8()?;
+        if res.planes >= 1 << PLANE_COUNT {
+            return Err(StateError::Invalid("planes"));
+        }
+        res.xo_chip = r.bool()?;
+        if res.xo_chip != (ram_size == XO_CHIP_RAM_SIZE) {
+            return Err(StateError::Invalid("RAM size"));
+        }
+        res.audio_pattern
+            .copy_from_slice(r.bytes(AUDIO_PATTERN_SIZE)?);
+        res.audio_pattern_loaded = r.bool()?;
+        res.pitch = r.u8()?;
+        res.vblank_wait = r.bool()?;
+        for key in res.input.iter_mut() {
+            *key = key_state(r.u8()?)?;
+        }
+        let clock_hz = r.u64()?;
+        if clock_hz == 0 {
+            return Err(StateError::Invalid("clock"));
+        }
+        res.change_clock(clock_hz);
+
+        res.quirks = r.quirks()?;
+
+        res.rom_size = r.u32()? as usize;
+        if res.rom_size > max_rom_size(res.xo_chip) {
+            return Err(StateError::Invalid("ROM size"));
+        }
+        res.rom_id = r.u64()?;
+
+        if r.version() >= 2 {
+            res.seed = r.u64()?;
+            res.rng = Rng::new(r.u64()?);
+        } else {
+            res.seed = self.seed;
+            res.rng = self.rng;
+        }
+
+        // The ROM is only needed for restarting, so it isn't part of the state.
+        if res.rom_id == self.rom_id {
+            res.rom = std::mem::take(&mut self.rom);
+        }
+
+        // Keep the debugger and display settings.
+        res.state = std::mem::replace(&mut self.state, ConsoleState::Paused);
+        res.breakpoints = std::mem::take(&mut self.breakpoints);
+        res.watchpoints = std::mem::take(&mut self.watchpoints);
+        res.trace = self.trace;
+        res.reduce_flicker = self.reduce_flicker;
+        res.reset = true;
+
+        *self = res;
+        Ok(())
+    }
+
+    pub fn is_reset(&mut self) -> bool {
+        let res = self.reset;
+        self.reset = false;
+        res
+    }
+
+    pub fn framebuffer(&self) -> &[DisplayPixel; NUM_PIXELS] {
+        &self.framebuffer
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut [DisplayPixel; NUM_PIXELS] {
+        &mut self.framebuffer
+    }
+
+    /// Width of the display in the current resolution.
+    pub fn display_width(&self) -> u32 {
+        if self.hires {
+            HIRES_DISPLAY_WIDTH
+        } else {
+            DISPLAY_WIDTH
+        }
+    }
+
+    /// Height of the display in the current resolution.
+    pub fn display_height(&self) -> u32 {
+        if self.hires {
+            HIRES_DISPLAY_HEIGHT
+        } else {
+            DISPLAY_HEIGHT
+        }
+    }
+
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    /// Enable XO-CHIP's instructions and 64KB of RAM.
+    ///
+    /// The extra memory is zeroed, so a loaded program keeps running.
+    pub fn set_xo_chip(&mut self, xo_chip: bool) {
+        self.xo_chip = xo_chip;
+        self.ram
+            .resize(if xo_chip { XO_CHIP_RAM_SIZE } else { RAM_SIZE }, 0);
+        // The history may refer to memory that is gone.
+        self.history.clear();
+    }
+
+    /// Use the quirks of a known interpreter.
+    ///
+    /// The XO-CHIP preset also enables XO-CHIP's extensions.
+    pub fn apply_preset(&mut self, preset: QuirkPreset) {
+        self.quirks = preset.quirks();
+        self.set_xo_chip(preset == QuirkPreset::XoChip);
+    }
+
+    /// Seed of the RNG used by CXNN.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Seed the RNG used by CXNN. The same seed gives the same numbers.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    /// Drawing planes selected by XO-CHIP's FN01 instruction.
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// XO-CHIP's 1-bit audio pattern, played while the sound timer is active.
+    ///
+    /// Returns `None` if the ROM never loaded a pattern with F002, in which case
+    /// the usual beep should be played.
+    pub fn audio_pattern(&self) -> Option<&[u8; AUDIO_PATTERN_SIZE]> {
+        if self.audio_pattern_loaded {
+            Some(&self.audio_pattern)
+        } else {
+            None
+        }
+    }
+
+    /// XO-CHIP's pitch register, set by FX3A.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn stack(&self) -> &[u16; STACK_SIZE] {
+        &self.stack
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> usize {
+        self.stack_ptr
+    }
+
+    pub fn rom_sz(&self) -> usize {
+        self.rom_size
+    }
+
+    /// Hash of the loaded ROM. Used to tell apart the files saved for each ROM.
+    pub fn rom_id(&self) -> u64 {
+        self.rom_id
+    }
+
+    pub fn rpl_flags(&self) -> &[u8; RPL_FLAG_COUNT] {
+        &self.rpl_flags
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAG_COUNT]) {
+        self.rpl_flags = flags;
+    }
+
+    /// Returns true if the RPL flags were written since the last call.
+    ///
+    /// Used to know when the flags should be saved to disk.
+    pub fn take_rpl_flags_dirty(&mut self) -> bool {
+        let res = self.rpl_flags_dirty;
+        self.rpl_flags_dirty = false;
+        res
+    }
+
+    pub fn registers(&self) -> &[u8; REGISTER_COUNT] {
+        &self.registers
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn paused(&self) -> bool {
+        self.state == ConsoleState::Paused
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn set_reduce_flicker(&mut self, reduce: bool) {
+        self.reduce_flicker = reduce;
+    }
+
+    pub fn pause(&mut self) {
+        self.state = ConsoleState::Paused;
+        self.goal = None;
+    }
+
+    pub fn run(&mut self) {
+        self.state = ConsoleState::Running;
+        self.goal = None;
+        self.fault = None;
+        self.stop = None;
+        self.resuming = true;
+    }
+
+    /// Run until the next instruction, treating a 2NNN call as one
+    /// instruction. The call runs until it returns to this stack frame.
+    pub fn step_over(&mut self) {
+        let call = matches!(
+            decode_at(&self.ram, self.pc as usize),
+            Some((Instruction::Call(_), _))
+        );
+
+        let goal = if call {
+            RunGoal::Address {
+                addr: self.pc.wrapping_add(2),
+                sp: Some(self.stack_ptr),
+            }
+        } else {
+            RunGoal::Step
+        };
+        self.run();
+        self.goal = Some(goal);
+    }
+
+    /// Run until the current subroutine returns, i.e. until the
+    /// matching 00EE pops the stack pointer below its current value.
+    ///
+    /// Does nothing outside of a subroutine.
+    pub fn step_out(&mut self) {
+        if self.stack_ptr == 0 {
+            return;
+        }
+
+        self.run();
+        self.goal = Some(RunGoal::Return { sp: self.stack_ptr });
+    }
+
+    /// Run until the PC reaches `addr`.
+    pub fn run_to(&mut self, addr: u16) {
+        self.run();
+        self.goal = Some(RunGoal::Address { addr, sp: None });
+    }
+
+    /// Returns true if the running goal was reached.
+    fn goal_reached(&self) -> bool {
+        match self.goal {
+            None => false,
+            Some(RunGoal::Step) => true,
+            Some(RunGoal::Address { addr, sp }) => {
+                self.pc == addr && sp.is_none_or(|sp| sp == self.stack_ptr)
+            }
+            Some(RunGoal::Return { sp }) => self.stack_ptr < sp,
+        }
+    }
+
+    /// Returns true if there is an executed instruction to step back over.
+    ///
+    /// Stepping back is not possible while a movie is recorded or played back,
+    /// or without `debug`, see `step_back`.
+    pub fn can_step_back(&self) -> bool {
+        !self.history.is_empty() && self.movie.is_none()
+    }
+
+    /// Undo the last executed instruction, restoring the state from before it.
+    ///
+    /// The history is only kept if the machine was created with `debug`,
+    /// otherwise there is never anything to undo. Only the last
+    /// `UNDO_HISTORY_SIZE` instructions can be undone, and loading a state
+    /// or ROM forgets them. Returns false if there is nothing to undo.
+    pub fn step_back(&mut self) -> bool {
+        if !self.can_step_back() {
+            return false;
+        }
+        let Some(undo) = self.history.pop_back() else {
+            return false;
+        };
+
+        for &(addr, old) in undo.ram.iter().rev() {
+            self.ram[addr] = old;
+        }
+        for &(idx, old) in undo.pixels.iter().rev() {
+            self.framebuffer[idx] = old;
+        }
+        self.set_cpu_state(undo.cpu);
+
+        self.pause();
+        self.fault = None;
+        self.stop = None;
+        self.reset = true;
+        true
+    }
+
+    /// The fault that paused the emulation, if any.
+    pub fn fault(&self) -> Option<&Chip8Error> {
+        self.fault.as_ref()
+    }
+
+    pub fn clear_fault(&mut self) {
+        self.fault = None;
+    }
+
+    /// Why the emulation last paused itself, if it did.
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.stop.as_ref()
+    }
+
+    /// Breakpoints by address. The emulation pauses before executing
+    /// an instruction with an enabled breakpoint.
+    pub fn breakpoints(&self) -> &BTreeMap<u16, Breakpoint> {
+        &self.breakpoints
+    }
+
+    /// Add an enabled breakpoint at `addr`.
+    ///
+    /// An existing breakpoint at `addr` is enabled.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.entry(addr).or_default().enabled = true;
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Only stop at the breakpoint at `addr` if `condition` holds.
+    pub fn set_breakpoint_condition(&mut self, addr: u16, condition: Option<Condition>) {
+        if let Some(breakpoint) = self.breakpoints.get_mut(&addr) {
+            breakpoint.condition = condition;
+        }
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, addr: u16, enabled: bool) {
+        if let Some(breakpoint) = self.breakpoints.get_mut(&addr) {
+            breakpoint.enabled = enabled;
+        }
+    }
+
+    /// Watchpoints on RAM. The emulation pauses after an instruction
+    /// accesses memory under an enabled watchpoint.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) {
+        if index < self.watchpoints.len() {
+            self.watchpoints.remove(index);
+        }
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(watchpoint) = self.watchpoints.get_mut(index) {
+            watchpoint.enabled = enabled;
+        }
+    }
+
+    /// Restart the ROM and record the session into a movie.
+    ///
+    /// Returns false if no ROM is loaded or it can't be restarted.
+    pub fn start_recording(&mut self) -> bool {
+        if self.rom.is_empty() || self.restart().is_err() {
+            return false;
+        }
+
+        let movie = Movie::new(
+            self.rom_id,
+            self.seed,
+            self.clock_hz,
+            self.xo_chip,
+            self.quirks,
+        );
+        self.movie = Some(MovieMode::Recording(movie));
+        true
+    }
+
+    /// Stop recording and return the movie.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieMode::Recording(movie)) => Some(movie),
+            other => {
+                self.movie = other;
+                None
+            }
+        }
+    }
+
+    /// Restart the ROM with the movie's settings and play it back.
+    ///
+    /// While playing, the movie's keys replace `input`. The emulation
+    /// pauses once the movie ends.
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), MovieError> {
+        if self.rom.is_empty() || movie.rom_id != self.rom_id {
+            return Err(MovieError::WrongRom);
+        }
+        if self.rom.len() > max_rom_size(movie.xo_chip) {
+            return Err(MovieError::Rom(FaultKind::RomTooLarge(self.rom.len())));
+        }
+
+        self.quirks = movie.quirks;
+        self.set_xo_chip(movie.xo_chip);
+        self.change_clock(movie.clock_hz);
+        self.set_seed(movie.seed);
+        self.restart().map_err(MovieError::Rom)?;
+        self.movie = Some(MovieMode::Playing {
+            movie,
+            cursor: MovieCursor::default(),
+        });
+        Ok(())
+    }
+
+    /// Stop recording or playing back a movie.
+    pub fn stop_movie(&mut self) {
+        self.movie = None;
+    }
+
+    pub fn recording(&self) -> bool {
+        matches!(self.movie, Some(MovieMode::Recording(_)))
+    }
+
+    /// Steps played and total steps of the movie being played back.
+    pub fn playback_progress(&self) -> Option<(usize, usize)> {
+        match &self.movie {
+            Some(MovieMode::Playing { movie, cursor }) => Some((cursor.pos, movie.len())),
+            _ => None,
+        }
+    }
+
+    /// Returns true while a movie is recorded or played back. Anything
+    /// changing the machine besides stepping it would break the movie.
+    pub fn movie_active(&self) -> bool {
+        self.movie.is_some()
+    }
+
+    /// Add a breakpoint at `addr`, or remove it if there is one already.
+    pub fn toggle_breakpoint(&mut self, addr: u16) {
+        if self.breakpoints.remove(&addr).is_none() {
+            self.add_breakpoint(addr);
+        }
+    }
+
+    /// Fetch, decode and execute the next instruction.
+    ///
+    /// Since we call this function more times than the cpu clock
+    /// we have a timer to check if it's time to actually process
+    /// the next instruction.
+    ///
+    /// On a fault the emulation is paused with the PC at the faulting
+    /// instruction. The fault is also kept until the emulation is resumed.
+    ///
+    /// With the display wait quirk the COSMAC VIP's behaviour is emulated,
+    /// where a draw blocks the CPU until the next 60Hz vertical blank.
+    /// Stepping by hand while paused never waits.
+    ///
+    /// While running, reaching a breakpoint pauses the emulation before the
+    /// instruction is executed. Resuming executes it without stopping again.
+    /// Watchpoints pause the emulation after the instruction is executed.
+    /// Stopping at a breakpoint changes nothing else, so the step is
+    /// repeated in full once resumed.
+    ///
+    /// While a movie is recorded every step is added to it. While one is
+    /// played back `delta` and the keys come from the movie instead.
+    pub fn step(&mut self, delta: Duration) -> Result<StepResult, Chip8Error> {
+        let mut step = MovieStep {
+            delta,
+            paused: self.state == ConsoleState::Paused,
+            input: self.input,
+        };
+        if let Some(MovieMode::Playing { movie, cursor }) = &self.movie {
+            match cursor.peek(movie) {
+                Some(next) => step = next,
+                None => {
+                    self.movie = None;
+                    self.pause();
+                    self.stop = Some(StopReason::MovieEnded);
+                    return Ok(StepResult {
+                        drawn: false,
+                        beep: false,
+                        stop: Some(StopReason::MovieEnded),
+                    });
+                }
+            }
+        }
+
+        let vblank = step.paused || self.timer_60hz.would_finish(step.delta);
+        let cpu_tick = step.paused || self.timer_clock.would_finish(step.delta);
+        let execute = cpu_tick && (vblank || !self.vblank_wait);
+
+        let pc = self.pc;
+        if execute && self.state == ConsoleState::Running && !self.resuming {
+            let breakpoint = self
+                .breakpoints
+                .get(&pc)
+                .is_some_and(|b| b.enabled && b.condition.as_ref().is_none_or(|c| c.eval(self)));
+            if breakpoint {
+                let stop = StopReason::Breakpoint(pc);
+                self.pause();
+                self.stop = Some(stop.clone());
+                return Ok(StepResult {
+                    drawn: false,
+                    beep: false,
+                    stop: Some(stop),
+                });
+            }
+        }
+
+        match &mut self.movie {
+            Some(MovieMode::Recording(movie)) => movie.push(step),
+            Some(MovieMode::Playing { movie, cursor }) => {
+                cursor.advance(movie);
+                self.input = step.input;
+            }
+            None => {}
+        }
+
+        self.timer_clock.tick(step.delta);
+        self.timer_60hz.tick(step.delta);
+        if vblank {
+            self.vblank_wait = false;
+        }
+
+        let mut drawn = false;
+        let mut stop = None;
+        if execute {
+            self.resuming = false;
+            self.watch_hit = None;
+
+            // Stepping back is part of the debugger, so only keep
+            // the history while it is shown.
+            if self.debug {
+                self.undo = Some(UndoEntry::new(self.cpu_state()));
+            }
+            let mut opcode = 0;
+            let res = self.fetch().and_then(|instr| {
+                opcode = instr;
+                self.execute(instr)
+            });
+            self.push_undo();
+
+            match res {
+                Ok(res) => drawn = res,
+                Err(kind) => {
+                    let err = Chip8Error { pc, opcode, kind };
+                    self.pc = pc;
+                    self.pause();
+                    self.fault = Some(err);
+                    return Err(err);
+                }
+            }
+
+            if self.quirks.display_wait && matches!(decode(opcode), Instruction::Draw { .. }) {
+                self.vblank_wait = !step.paused;
+            }
+
+            if let Some(hit) = self.watch_hit.take() {
+                stop = Some(StopReason::Watchpoint(WatchHit { pc, ..hit }));
+            } else if self.goal_reached() {
+                stop = Some(StopReason::Reached(self.pc));
+            }
+
+            if stop.is_some() {
+                self.pause();
+                self.stop = stop.clone();
+            }
+        }
+
+        if vblank {
+            if self.delay_timer > 0 {
+                self.delay_timer -= 1;
+            }
+            if self.sound_timer > 0 {
+                self.sound_timer -= 1;
+            }
+        }
+
+        // Don't play sound when paused as it might be unpleasant.
+        Ok(StepResult {
+            drawn,
+            beep: self.state == ConsoleState::Running && self.sound_timer > 0,
+            stop,
+        })
+    }
+
+    /// Change the CPU clock.
+    ///
+    /// Some games may need a higher clock speed, others may be
+    /// more playable at lower than the default.
+    pub fn change_clock(&mut self, clock_hz: u64) {
+        if clock_hz == self.clock_hz {
+            return;
+        }
+
+        self.clock_hz = clock_hz;
+        self.timer_clock = Timer::new(Duration::from_nanos(SECOND_IN_NS / clock_hz));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::STATE_VERSION;
+
+    const DELTA: Duration = Duration::from_nanos(1_000_000_000 / 600);
+
+    /// Draws random sprites, so the RNG, RAM, registers and display all
+    /// change while it runs.
+    const ROM: [u8; 14] = [
+        0xC0, 0xFF, // V0 = rand
+        0xC1, 0x1F, // V1 = rand & 0x1F
+        0xF0, 0x29, // I = font digit V0
+        0xD0, 0x15, // draw at V0, V1
+        0xA3, 0x00, // I = 0x300
+        0xF1, 0x55, // store V0, V1
+        0x12, 0x00, // jump 0x200
+    ];
+
+    fn running_machine() -> Chip8 {
+        let mut chip8 = Chip8::new(600, false);
+        chip8.set_seed(42);
+        chip8.insert_cartridge(&ROM).unwrap();
+        chip8.run();
+        for _ in 0..100 {
+            chip8.step(DELTA).unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn save_state_round_trip() {
+        let mut chip8 = running_machine();
+        let state = chip8.save_state();
+
+        let mut loaded = Chip8::new(600, false);
+        loaded.load_state(&state).unwrap();
+        assert_eq!(loaded.save_state(), state);
+        assert_eq!(loaded.seed(), 42);
+
+        // Both go on the same way, random numbers included.
+        loaded.run();
+        for _ in 0..100 {
+            chip8.step(DELTA).unwrap();
+            loaded.step(DELTA).unwrap();
+        }
+        assert_eq!(loaded.save_state(), chip8.save_state());
+    }
+
+    #[test]
+    fn load_version_1_state() {
+        let chip8 = running_machine();
+        let mut state = chip8.save_state();
+
+        // Version 1 had no seed and RNG state at the end.
+        state.truncate(state.len() - 16);
+        state[4..6].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut loaded = Chip8::new(600, false);
+        loaded.set_seed(7);
+        loaded.load_state(&state).unwrap();
+        assert_eq!(loaded.seed(), 7);
+        assert_eq!(loaded.pc(), chip8.pc());
+        assert_eq!(loaded.registers(), chip8.registers());
+        assert!(loaded.framebuffer() == chip8.framebuffer());
+
+        // Saving writes the current version again.
+        let saved = loaded.save_state();
+        assert_eq!(saved[4..6], STATE_VERSION.to_le_bytes());
+        assert_eq!(saved.len(), state.len() + 16);
+    }
+
+    #[test]
+    fn load_state_errors() {
+        let state = running_machine().save_state();
+        let mut chip8 = Chip8::new(600, false);
+
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(chip8.load_state(&bad_magic), Err(StateError::BadMagic));
+
+        let mut newer = state.clone();
+        newer[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            chip8.load_state(&newer),
+            Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+        );
+
+        assert_eq!(
+            chip8.load_state(&state[..state.len() - 1]),
+            Err(StateError::Truncated)
+        );
+    }
+
+    #[test]
+    fn step_over_runs_the_whole_call() {
+        // CALL 0x206, LD V1, 1, then at 0x206: LD V0, 5, RET.
+        let rom = [0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x05, 0x00, 0xEE];
+        let mut chip8 = Chip8::new(600, false);
+        chip8.insert_cartridge(&rom).unwrap();
+
+        chip8.step_over();
+        let mut stop = None;
+        for _ in 0..10 {
+            stop = chip8.step(DELTA).unwrap().stop;
+            if stop.is_some() {
+                break;
+            }
+        }
+        assert_eq!(stop, Some(StopReason::Reached(0x202)));
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.registers()[0], 5);
+    }
+
+    #[test]
+    fn skip_over_long_load() {
+        // SE V0, 0 skips F000 NNNN whole with XO-CHIP, else just F000.
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34];
+        for (xo_chip, pc) in [(true, 0x206), (false, 0x204)] {
+            let mut chip8 = Chip8::new(600, false);
+            chip8.set_xo_chip(xo_chip);
+            chip8.insert_cartridge(&rom).unwrap();
+            chip8.run();
+            chip8.step(DELTA).unwrap();
+            assert_eq!(chip8.pc(), pc);
+        }
+    }
+
+    #[test]
+    fn load_corrupted_state() {
+        let mut chip8 = running_machine();
+        let state = chip8.save_state();
+
+        // Header, RAM size, RAM, stack and stack pointer come first.
+        let framebuffer = 6 + 4 + RAM_SIZE + 2 * STACK_SIZE + 1;
+        let planes =
+            framebuffer + 2 * NUM_PIXELS + 2 + 2 + 1 + 1 + REGISTER_COUNT + RPL_FLAG_COUNT + 1;
+        let rom_size = state.len() - 16 - 8 - 4;
+
+        let mut pixel = state.clone();
+        pixel[framebuffer + 2 * 100] = 4;
+        assert_eq!(chip8.load_state(&pixel), Err(StateError::Invalid("pixel")));
+
+        let mut selector = state.clone();
+        assert_eq!(selector[planes], 1);
+        selector[planes] = 0xFF;
+        assert_eq!(
+            chip8.load_state(&selector),
+            Err(StateError::Invalid("planes"))
+        );
+
+        let mut rom = state.clone();
+        rom[rom_size..rom_size + 4].copy_from_slice(&(RAM_SIZE as u32).to_le_bytes());
+        assert_eq!(chip8.load_state(&rom), Err(StateError::Invalid("ROM size")));
+
+        // A state that fails to load leaves the machine as it was.
+        assert_eq!(chip8.save_state(), state);
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+
+use crate::chip8::Chip8;
+
+/// A boolean expression over the CPU state, used by conditional breakpoints.
+///
+/// Examples: `V3 == 0x10 && I > 0x300`, `DT == 0`, `!(VF || SP > 2)`.
+///
+/// The operands are numbers, in decimal or hex with a `0x` prefix, and
+/// the registers `V0`-`VF`, `I`, `PC`, `DT`, `ST` and `SP`. The operators
+/// are `==`, `!=`, `<`, `<=`, `>`, `>=`, `&&`, `||` and `!`, with the usual
+/// precedence. Like in C, any non-zero value is true. Comparisons can't
+/// be chained, `1 < V0 < 5` is written as `1 < V0 && V0 < 5`.
+#[derive(Clone, Debug)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+/// Error in a condition's source. `pos` is the byte offset of the error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConditionError {
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+    Sp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(u32),
+    Operand(Operand),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Num(u32),
+    Operand(Operand),
+    Op(BinOp),
+    Not,
+    LParen,
+    RParen,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let start = pos;
+        if c.is_ascii_alphanumeric() {
+            while pos < bytes.len() && bytes[pos].is_ascii_alphanumeric() {
+                pos += 1;
+            }
+            let word = source[start..pos].to_ascii_uppercase();
+            let token = if c.is_ascii_digit() {
+                let num = match word.strip_prefix("0X") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                Token::Num(num.map_err(|_| ConditionError {
+                    pos: start,
+                    msg: "Invalid number",
+                })?)
+            } else {
+                Token::Operand(operand(&word).ok_or(ConditionError {
+                    pos: start,
+                    msg: "Unknown register",
+                })?)
+            };
+            tokens.push((start, token));
+            continue;
+        }
+
+        let next = bytes.get(pos + 1).copied();
+        let (token, len) = match (c, next) {
+            (b'=', Some(b'=')) => (Token::Op(BinOp::Eq), 2),
+            (b'!', Some(b'=')) => (Token::Op(BinOp::Ne), 2),
+            (b'<', Some(b'=')) => (Token::Op(BinOp::Le), 2),
+            (b'>', Some(b'=')) => (Token::Op(BinOp::Ge), 2),
+            (b'&', Some(b'&')) => (Token::Op(BinOp::And), 2),
+            (b'|', Some(b'|')) => (Token::Op(BinOp::Or), 2),
+            (b'<', _) => (Token::Op(BinOp::Lt), 1),
+            (b'>', _) => (Token::Op(BinOp::Gt), 1),
+            (b'!', _) => (Token::Not, 1),
+            (b'(', _) => (Token::LParen, 1),
+            (b')', _) => (Token::RParen, 1),
+            _ => {
+                return Err(ConditionError {
+                    pos,
+                    msg: "Unexpected character",
+                })
+            }
+        };
+        tokens.push((start, token));
+        pos += len;
+    }
+
+    Ok(tokens)
+}
+
+fn operand(word: &str) -> Option<Operand> {
+    match word {
+        "I" => Some(Operand::I),
+        "PC" => Some(Operand::Pc),
+        "DT" => Some(Operand::Dt),
+        "ST" => Some(Operand::St),
+        "SP" => Some(Operand::Sp),
+        _ => {
+            let reg = word.strip_prefix('V')?;
+            if reg.len() != 1 {
+                return None;
+            }
+            usize::from_str_radix(reg, 16).ok().map(Operand::V)
+        }
+    }
+}
+
+/// Recursive descent parser. Each level handles one precedence,
+/// from `||` being the loosest to `!` and parentheses.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).map(|&(_, token)| token)
+    }
+
+    /// Byte offset of the current token, used for errors.
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |&(pos, _)| pos)
+    }
+
+    fn error(&self, msg: &'static str) -> ConditionError {
+        ConditionError {
+            pos: self.offset(),
+            msg,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[BinOp],
+        next: fn(&mut Self) -> Result<Expr, ConditionError>,
+    ) -> Result<Expr, ConditionError> {
+        let mut lhs = next(self)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, ConditionError> {
+        self.binary(&[BinOp::Or], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, ConditionError> {
+        self.binary(&[BinOp::And], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ConditionError> {
+        const OPS: [BinOp; 6] = [
+            BinOp::Eq,
+            BinOp::Ne,
+            BinOp::Lt,
+            BinOp::Le,
+            BinOp::Gt,
+            BinOp::Ge,
+        ];
+        let lhs = self.unary()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) if OPS.contains(&op) => op,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = self.unary()?;
+        if matches!(self.peek(), Some(Token::Op(op)) if OPS.contains(&op)) {
+            return Err(self.error("Chained comparison"));
+        }
+
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        let token = self.peek().ok_or_else(|| self.error("Expected a value"))?;
+        self.pos += 1;
+        match token {
+            Token::Num(num) => Ok(Expr::Num(num)),
+            Token::Operand(operand) => Ok(Expr::Operand(operand)),
+            Token::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::LParen => {
+                let expr = self.or()?;
+                if self.peek() != Some(Token::RParen) {
+                    return Err(self.error("Expected ')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::RParen | Token::Op(_) => {
+                self.pos -= 1;
+                Err(self.error("Expected a value"))
+            }
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, chip8: &Chip8) -> u32 {
+        match self {
+            Expr::Num(num) => *num,
+            Expr::Operand(operand) => match *operand {
+                Operand::V(reg) => chip8.registers()[reg] as u32,
+                Operand::I => chip8.index_register() as u32,
+                Operand::Pc => chip8.pc() as u32,
+                Operand::Dt => chip8.delay_timer() as u32,
+                Operand::St => chip8.sound_timer() as u32,
+                Operand::Sp => chip8.sp() as u32,
+            },
+            Expr::Not(expr) => (expr.eval(chip8) == 0) as u32,
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(chip8);
+                // `&&` and `||` short-circuit, mostly to save time.
+                let res = match op {
+                    BinOp::And => lhs != 0 && rhs.eval(chip8) != 0,
+                    BinOp::Or => lhs != 0 || rhs.eval(chip8) != 0,
+                    BinOp::Eq => lhs == rhs.eval(chip8),
+                    BinOp::Ne => lhs != rhs.eval(chip8),
+                    BinOp::Lt => lhs < rhs.eval(chip8),
+                    BinOp::Le => lhs <= rhs.eval(chip8),
+                    BinOp::Gt => lhs > rhs.eval(chip8),
+                    BinOp::Ge => lhs >= rhs.eval(chip8),
+                };
+                res as u32
+            }
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, ConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            end: source.len(),
+        };
+
+        let expr = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Unexpected token"));
+        }
+
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Returns true if the condition holds for the current CPU state.
+    pub fn eval(&self, chip8: &Chip8) -> bool {
+        self.expr.eval(chip8) != 0
+    }
+
+    /// The condition as it was written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str) -> bool {
+        Condition::parse(source)
+            .unwrap()
+            .eval(&Chip8::new(600, false))
+    }
+
+    fn tree(source: &str) -> String {
+        format!("{:?}", Condition::parse(source).unwrap().expr)
+    }
+
+    fn error(source: &str) -> ConditionError {
+        Condition::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(tree("1 || 0 && 0"), tree("1 || (0 && 0)"));
+        assert_eq!(tree("0 && 1 || 1"), tree("(0 && 1) || 1"));
+        assert_eq!(tree("V0 == 1 && V1 < 2"), tree("(V0 == 1) && (V1 < 2)"));
+        assert_eq!(tree("!V0 == 1"), tree("(!V0) == 1"));
+        assert_eq!(tree("!!V0"), tree("!(!V0)"));
+
+        assert!(eval("1 || 0 && 0"));
+        assert!(!eval("!5 == 1"));
+        assert!(eval("!(5 == 1)"));
+    }
+
+    #[test]
+    fn literals() {
+        assert!(eval("0x10 == 16"));
+        assert!(eval("0XfF == 255"));
+        assert!(eval("PC == 0x200"));
+        assert!(eval("v0 == 0 && vf == 0"));
+        assert!(!eval("0"));
+        assert!(eval("4294967295"));
+    }
+
+    #[test]
+    fn error_positions() {
+        let cases = [
+            ("", 0, "Expected a value"),
+            ("V0 ==", 5, "Expected a value"),
+            ("V0 == 0x1G", 6, "Invalid number"),
+            ("4294967296", 0, "Invalid number"),
+            ("VG == 1", 0, "Unknown register"),
+            ("V0 = 1", 3, "Unexpected character"),
+            ("(V0 == 1", 8, "Expected ')'"),
+            ("V0 == 1)", 7, "Unexpected token"),
+            ("V0 == 1 2", 8, "Unexpected token"),
+            ("1 < V0 < 5", 7, "Chained comparison"),
+            ("V0 == V1 != 0", 9, "Chained comparison"),
+        ];
+        for (source, pos, msg) in cases {
+            assert_eq!(error(source), ConditionError { pos, msg }, "{}", source);
+        }
+    }
+
+    #[test]
+    fn chained_comparisons_in_parentheses() {
+        assert!(eval("(1 < 2) < 3"));
+        assert!(eval("1 < 2 && 2 < 3"));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for source in ["V3 == 0x10 && I > 0x300", "DT == 0", "!(VF || SP > 2)"] {
+            let cond = Condition::parse(source).unwrap();
+            assert_eq!(cond.to_string(), source);
+
+            let again = Condition::parse(&cond.to_string()).unwrap();
+            assert_eq!(again.to_string(), source);
+            assert_eq!(format!("{:?}", again.expr), format!("{:?}", cond.expr));
+        }
+
+        assert_eq!(
+            Condition::parse("  DT == 0 ").unwrap().to_string(),
+            "DT == 0"
+        );
+    }
+}
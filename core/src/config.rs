@@ -0,0 +1,79 @@
+use std::ops::Range;
+
+/// Chip-8's Config
+/// Display
+pub const DISPLAY_WIDTH: u32 = 64;
+pub const DISPLAY_HEIGHT: u32 = 32;
+/// SUPER-CHIP's high resolution display
+pub const HIRES_DISPLAY_WIDTH: u32 = 128;
+pub const HIRES_DISPLAY_HEIGHT: u32 = 64;
+/// XO-CHIP's bitplanes. Each combination of them is a different colour.
+pub const PLANE_COUNT: usize = 2;
+
+/// Memory sizes
+pub const RAM_SIZE: usize = 4096;
+pub const XO_CHIP_RAM_SIZE: usize = 65536;
+pub const STACK_SIZE: usize = 16;
+pub const REGISTER_COUNT: usize = 16;
+/// HP48 RPL user flags. SUPER-CHIP uses 8 of them, XO-CHIP all 16.
+pub const RPL_FLAG_COUNT: usize = 16;
+pub const SCHIP_RPL_FLAG_COUNT: usize = 8;
+
+/// XO-CHIP's audio pattern is 16 bytes, i.e. 128 1-bit samples.
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// Pitch at which the audio pattern plays at 4000 samples per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// Instructions that can be stepped back in the debugger.
+pub const UNDO_HISTORY_SIZE: usize = 10000;
+
+/// Input related
+pub const NUM_KEYS: usize = 16;
+
+/// Starting programm address in Chip8's RAM.
+pub const START_PC: usize = 0x200;
+
+/// Font used by Chip8. Change for something more fancy
+pub const FONT: [u8; 5 * 16] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+/// Address range for Chip8's font
+pub const FONT_RANGE: Range<usize> = 0x50..0xA0;
+
+/// SUPER-CHIP's big font. Only has the digits 0-9 originally,
+/// the letters A-F are taken from XO-CHIP.
+pub const BIG_FONT: [u8; 10 * 16] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+/// Address range for SUPER-CHIP's big font. Placed right after the small one.
+pub const BIG_FONT_RANGE: Range<usize> = 0xA0..0x140;
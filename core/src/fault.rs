@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// What went wrong while executing an instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultKind {
+    UnknownOpcode,
+    StackOverflow,
+    StackUnderflow,
+    /// Memory access outside of RAM, at the given address.
+    OutOfBounds(usize),
+    /// A ROM of the given size doesn't fit in RAM after 0x200.
+    RomTooLarge(usize),
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultKind::UnknownOpcode => write!(f, "Unknown opcode"),
+            FaultKind::StackOverflow => write!(f, "Stack overflow"),
+            FaultKind::StackUnderflow => write!(f, "Stack underflow"),
+            FaultKind::OutOfBounds(addr) => {
+                write!(f, "Out-of-bounds memory access at 0x{:04x}", addr)
+            }
+            FaultKind::RomTooLarge(size) => {
+                write!(f, "ROM too large for this memory size ({} bytes)", size)
+            }
+        }
+    }
+}
+
+/// A fault raised by the interpreter.
+///
+/// `pc` is the address of the faulting instruction and `opcode` the
+/// instruction itself. `opcode` is 0 if it couldn't be read from memory.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Chip8Error {
+    pub pc: u16,
+    pub opcode: u16,
+    pub kind: FaultKind,
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (opcode 0x{:04x} at PC 0x{:04x})",
+            self.kind, self.opcode, self.pc
+        )
+    }
+}
+
+impl std::error::Error for Chip8Error {}
@@ -0,0 +1,46 @@
+use crate::chip8::{DisplayPixel, KeyState};
+use crate::config::{AUDIO_PATTERN_SIZE, NUM_KEYS, REGISTER_COUNT, RPL_FLAG_COUNT, STACK_SIZE};
+use crate::rng::Rng;
+
+/// The machine's state besides RAM and the framebuffer.
+///
+/// It is small enough to be copied before every instruction.
+#[derive(Clone, Copy)]
+pub(crate) struct CpuState {
+    pub pc: u16,
+    pub index_register: u16,
+    pub stack: [u16; STACK_SIZE],
+    pub stack_ptr: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub registers: [u8; REGISTER_COUNT],
+    pub rpl_flags: [u8; RPL_FLAG_COUNT],
+    pub hires: bool,
+    pub planes: u8,
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub audio_pattern_loaded: bool,
+    pub pitch: u8,
+    pub vblank_wait: bool,
+    pub input: [KeyState; NUM_KEYS],
+    pub rng: Rng,
+}
+
+/// What an instruction changed, so it can be undone.
+///
+/// `ram` and `pixels` hold the old values of the changed bytes and
+/// pixels, in the order they were changed.
+pub(crate) struct UndoEntry {
+    pub cpu: CpuState,
+    pub ram: Vec<(usize, u8)>,
+    pub pixels: Vec<(usize, DisplayPixel)>,
+}
+
+impl UndoEntry {
+    pub fn new(cpu: CpuState) -> UndoEntry {
+        UndoEntry {
+            cpu,
+            ram: Vec::new(),
+            pixels: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use std::fmt;
+
+use crate::quirks::Quirks;
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+///
+/// `x` and `y` are register indices, `addr` is a 12-bit address.
+/// Decoding doesn't depend on the interpreter's state, so XO-CHIP
+/// instructions are decoded even when XO-CHIP is disabled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Instruction {
+    /// 00CN
+    ScrollDown(u8),
+    /// 00E0
+    Clear,
+    /// 00EE
+    Return,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FE
+    Lores,
+    /// 00FF
+    Hires,
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipEqByte {
+        x: u8,
+        byte: u8,
+    },
+    /// 4XNN
+    SkipNeByte {
+        x: u8,
+        byte: u8,
+    },
+    /// 5XY0
+    SkipEqReg {
+        x: u8,
+        y: u8,
+    },
+    /// 5XY2
+    SaveRange {
+        x: u8,
+        y: u8,
+    },
+    /// 5XY3
+    LoadRange {
+        x: u8,
+        y: u8,
+    },
+    /// 6XNN
+    LoadByte {
+        x: u8,
+        byte: u8,
+    },
+    /// 7XNN
+    AddByte {
+        x: u8,
+        byte: u8,
+    },
+    /// 8XY0
+    Move {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY1
+    Or {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY2
+    And {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY3
+    Xor {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY4
+    Add {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY5
+    Sub {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY6
+    ShiftRight {
+        x: u8,
+        y: u8,
+    },
+    /// 8XY7
+    SubN {
+        x: u8,
+        y: u8,
+    },
+    /// 8XYE
+    ShiftLeft {
+        x: u8,
+        y: u8,
+    },
+    /// 9XY0
+    SkipNeReg {
+        x: u8,
+        y: u8,
+    },
+    /// ANNN
+    LoadIndex(u16),
+    /// BNNN. `x` is only used with the jumping quirk.
+    JumpOffset {
+        x: u8,
+        addr: u16,
+    },
+    /// CXNN
+    Random {
+        x: u8,
+        byte: u8,
+    },
+    /// DXYN
+    Draw {
+        x: u8,
+        y: u8,
+        n: u8,
+    },
+    /// EX9E
+    SkipKey(u8),
+    /// EXA1
+    SkipNotKey(u8),
+    /// F000 NNNN. The address is the word following the instruction.
+    LongLoadIndex,
+    /// FN01
+    Plane(u8),
+    /// F002
+    Audio,
+    /// FX07
+    LoadDelay(u8),
+    /// FX0A
+    WaitKey(u8),
+    /// FX15
+    SetDelay(u8),
+    /// FX18
+    SetSound(u8),
+    /// FX1E
+    AddIndex(u8),
+    /// FX29
+    Font(u8),
+    /// FX30
+    BigFont(u8),
+    /// FX33
+    Bcd(u8),
+    /// FX3A
+    Pitch(u8),
+    /// FX55
+    Store(u8),
+    /// FX65
+    Load(u8),
+    /// FX75
+    SaveFlags(u8),
+    /// FX85
+    LoadFlags(u8),
+    Unknown(u16),
+}
+
+impl Instruction {
+    /// The mnemonic of the instruction as executed with `quirks`.
+    ///
+    /// Only BNNN differs from `to_string`, as the jumping quirk
+    /// makes it jump to XNN + VX.
+    pub fn mnemonic(&self, quirks: &Quirks) -> String {
+        match *self {
+            Instruction::JumpOffset { x, addr } if quirks.jumping => {
+                format!("JP V{:X}, 0x{:03X}", x, addr)
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Decode a 2-byte opcode.
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let byte = (opcode & 0x00FF) as u8;
+    let addr = opcode & 0x0FFF;
+
+    match (opcode & 0xF000) >> 12 {
+        0 => match addr {
+            0x0C0..=0x0CF => Instruction::ScrollDown(n),
+            0x0E0 => Instruction::Clear,
+            0x0EE => Instruction::Return,
+            0x0FB => Instruction::ScrollRight,
+            0x0FC => Instruction::ScrollLeft,
+            0x0FE => Instruction::Lores,
+            0x0FF => Instruction::Hires,
+            _ => Instruction::Unknown(opcode),
+        },
+        1 => Instruction::Jump(addr),
+        2 => Instruction::Call(addr),
+        3 => Instruction::SkipEqByte { x, byte },
+        4 => Instruction::SkipNeByte { x, byte },
+        5 => match n {
+            0 => Instruction::SkipEqReg { x, y },
+            2 => Instruction::SaveRange { x, y },
+            3 => Instruction::LoadRange { x, y },
+            _ => Instruction::Unknown(opcode),
+        },
+        6 => Instruction::LoadByte { x, byte },
+        7 => Instruction::AddByte { x, byte },
+        8 => match n {
+            0 => Instruction::Move { x, y },
+            1 => Instruction::Or { x, y },
+            2 => Instruction::And { x, y },
+            3 => Instruction::Xor { x, y },
+            4 => Instruction::Add { x, y },
+            5 => Instruction::Sub { x, y },
+            6 => Instruction::ShiftRight { x, y },
+            7 => Instruction::SubN { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => Instruction::Unknown(opcode),
+        },
+        9 if n == 0 => Instruction::SkipNeReg { x, y },
+        0xA => Instruction::LoadIndex(addr),
+        0xB => Instruction::JumpOffset { x, addr },
+        0xC => Instruction::Random { x, byte },
+        0xD => Instruction::Draw { x, y, n },
+        0xE => match byte {
+            0x9E => Instruction::SkipKey(x),
+            0xA1 => Instruction::SkipNotKey(x),
+            _ => Instruction::Unknown(opcode),
+        },
+        0xF => match byte {
+            0x00 if x == 0 => Instruction::LongLoadIndex,
+            0x01 => Instruction::Plane(x),
+            0x02 if x == 0 => Instruction::Audio,
+            0x07 => Instruction::LoadDelay(x),
+            0x0A => Instruction::WaitKey(x),
+            0x15 => Instruction::SetDelay(x),
+            0x18 => Instruction::SetSound(x),
+            0x1E => Instruction::AddIndex(x),
+            0x29 => Instruction::Font(x),
+            0x30 => Instruction::BigFont(x),
+            0x33 => Instruction::Bcd(x),
+            0x3A => Instruction::Pitch(x),
+            0x55 => Instruction::Store(x),
+            0x65 => Instruction::Load(x),
+            0x75 => Instruction::SaveFlags(x),
+            0x85 => Instruction::LoadFlags(x),
+            _ => Instruction::Unknown(opcode),
+        },
+        _ => Instruction::Unknown(opcode),
+    }
+}
+
+/// Decode the instruction stored at `addr`.
+///
+/// Returns the instruction and its length in bytes, which is 4 for
+/// XO-CHIP's F000 NNNN as the address follows the opcode.
+/// `None` if the opcode is outside of `ram`.
+pub fn decode_at(ram: &[u8], addr: usize) -> Option<(Instruction, usize)> {
+    let bytes = ram.get(addr..addr + 2)?;
+    let instruction = decode(u16::from_be_bytes([bytes[0], bytes[1]]));
+    let len = if instruction == Instruction::LongLoadIndex {
+        4
+    } else {
+        2
+    };
+
+    Some((instruction, len))
+}
+
+/// Standard CHIP-8 mnemonics, e.g. `LD V3, 0x1F` or `DRW V0, V1, 5`.
+///
+/// SUPER-CHIP and XO-CHIP instructions follow the usual extensions
+/// to them. Unknown opcodes are shown as raw data words. BNNN is shown
+/// as the original `JP V0, addr`, see `mnemonic` for the jumping quirk.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jump(addr) => write!(f, "JP 0x{:03X}", addr),
+            Instruction::Call(addr) => write!(f, "CALL 0x{:03X}", addr),
+            Instruction::SkipEqByte { x, byte } => write!(f, "SE V{:X}, 0x{:02X}", x, byte),
+            Instruction::SkipNeByte { x, byte } => write!(f, "SNE V{:X}, 0x{:02X}", x, byte),
+            Instruction::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadByte { x, byte } => write!(f, "LD V{:X}, 0x{:02X}", x, byte),
+            Instruction::AddByte { x, byte } => write!(f, "ADD V{:X}, 0x{:02X}", x, byte),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex(addr) => write!(f, "LD I, 0x{:03X}", addr),
+            Instruction::JumpOffset { addr, .. } => write!(f, "JP V0, 0x{:03X}", addr),
+            Instruction::Random { x, byte } => write!(f, "RND V{:X}, 0x{:02X}", x, byte),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LongLoadIndex => write!(f, "LD I, LONG"),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::Store(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown(opcode) => write!(f, "DW 0x{:04X}", opcode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::QuirkPreset;
+
+    #[test]
+    fn decode_and_display() {
+        let cases = [
+            (0x00C5, Instruction::ScrollDown(5), "SCD 5"),
+            (0x00E0, Instruction::Clear, "CLS"),
+            (0x00EE, Instruction::Return, "RET"),
+            (0x00FB, Instruction::ScrollRight, "SCR"),
+            (0x00FC, Instruction::ScrollLeft, "SCL"),
+            (0x00FE, Instruction::Lores, "LOW"),
+            (0x00FF, Instruction::Hires, "HIGH"),
+            (0x1ABC, Instruction::Jump(0xABC), "JP 0xABC"),
+            (0x2ABC, Instruction::Call(0xABC), "CALL 0xABC"),
+            (
+                0x3A12,
+                Instruction::SkipEqByte { x: 0xA, byte: 0x12 },
+                "SE VA, 0x12",
+            ),
+            (
+                0x4A12,
+                Instruction::SkipNeByte { x: 0xA, byte: 0x12 },
+                "SNE VA, 0x12",
+            ),
+            (
+                0x5AB0,
+                Instruction::SkipEqReg { x: 0xA, y: 0xB },
+                "SE VA, VB",
+            ),
+            (
+                0x5AB2,
+                Instruction::SaveRange { x: 0xA, y: 0xB },
+                "SAVE VA - VB",
+            ),
+            (
+                0x5AB3,
+                Instruction::LoadRange { x: 0xA, y: 0xB },
+                "LOAD VA - VB",
+            ),
+            (
+                0x6A12,
+                Instruction::LoadByte { x: 0xA, byte: 0x12 },
+                "LD VA, 0x12",
+            ),
+            (
+                0x7A12,
+                Instruction::AddByte { x: 0xA, byte: 0x12 },
+                "ADD VA, 0x12",
+            ),
+            (0x8AB0, Instruction::Move { x: 0xA, y: 0xB }, "LD VA, VB"),
+            (0x8AB1, Instruction::Or { x: 0xA, y: 0xB }, "OR VA, VB"),
+            (0x8AB2, Instruction::And { x: 0xA, y: 0xB }, "AND VA, VB"),
+            (0x8AB3, Instruction::Xor { x: 0xA, y: 0xB }, "XOR VA, VB"),
+            (0x8AB4, Instruction::Add { x: 0xA, y: 0xB }, "ADD VA, VB"),
+            (0x8AB5, Instruction::Sub { x: 0xA, y: 0xB }, "SUB VA, VB"),
+            (
+                0x8AB6,
+                Instruction::ShiftRight { x: 0xA, y: 0xB },
+                "SHR VA, VB",
+            ),
+            (0x8AB7, Instruction::SubN { x: 0xA, y: 0xB }, "SUBN VA, VB"),
+            (
+                0x8ABE,
+                Instruction::ShiftLeft { x: 0xA, y: 0xB },
+                "SHL VA, VB",
+            ),
+            (
+                0x9AB0,
+                Instruction::SkipNeReg { x: 0xA, y: 0xB },
+                "SNE VA, VB",
+            ),
+            (0xAABC, Instruction::LoadIndex(0xABC), "LD I, 0xABC"),
+            (
+                0xBABC,
+                Instruction::JumpOffset {
+                    x: 0xA,
+                    addr: 0xABC,
+                },
+                "JP V0, 0xABC",
+            ),
+            (
+                0xCA12,
+                Instruction::Random { x: 0xA, byte: 0x12 },
+                "RND VA, 0x12",
+            ),
+            (
+                0xDAB5,
+                Instruction::Draw {
+                    x: 0xA,
+                    y: 0xB,
+                    n: 5,
+                },
+                "DRW VA, VB, 5",
+            ),
+            (0xEA9E, Instruction::SkipKey(0xA), "SKP VA"),
+            (0xEAA1, Instruction::SkipNotKey(0xA), "SKNP VA"),
+            (0xF000, Instruction::LongLoadIndex, "LD I, LONG"),
+            (0xF201, Instruction::Plane(2), "PLANE 2"),
+            (0xF002, Instruction::Audio, "AUDIO"),
+            (0xFA07, Instruction::LoadDelay(0xA), "LD VA, DT"),
+            (0xFA0A, Instruction::WaitKey(0xA), "LD VA, K"),
+            (0xFA15, Instruction::SetDelay(0xA), "LD DT, VA"),
+            (0xFA18, Instruction::SetSound(0xA), "LD ST, VA"),
+            (0xFA1E, Instruction::AddIndex(0xA), "ADD I, VA"),
+            (0xFA29, Instruction::Font(0xA), "LD F, VA"),
+            (0xFA30, Instruction::BigFont(0xA), "LD HF, VA"),
+            (0xFA33, Instruction::Bcd(0xA), "LD B, VA"),
+            (0xFA3A, Instruction::Pitch(0xA), "PITCH VA"),
+            (0xFA55, Instruction::Store(0xA), "LD [I], VA"),
+            (0xFA65, Instruction::Load(0xA), "LD VA, [I]"),
+            (0xFA75, Instruction::SaveFlags(0xA), "LD R, VA"),
+            (0xFA85, Instruction::LoadFlags(0xA), "LD VA, R"),
+        ];
+        for (opcode, instruction, text) in cases {
+            assert_eq!(decode(opcode), instruction, "0x{:04X}", opcode);
+            assert_eq!(instruction.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn invalid_encodings() {
+        let opcodes = [
+            0x0000, 0x00E1, 0x0123, 0x5AB1, 0x5AB4, 0x8AB8, 0x8ABF, 0x9AB1, 0xEA9F, 0xEAA2, 0xF100,
+            0xF102, 0xFA08, 0xFAFF,
+        ];
+        for opcode in opcodes {
+            assert_eq!(decode(opcode), Instruction::Unknown(opcode));
+        }
+        assert_eq!(decode(0xF100).to_string(), "DW 0xF100");
+    }
+
+    #[test]
+    fn decode_at_lengths() {
+        let ram = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xA1];
+        assert_eq!(decode_at(&ram, 0), Some((Instruction::LongLoadIndex, 4)));
+        assert_eq!(decode_at(&ram, 2), Some((Instruction::Jump(0x234), 2)));
+        assert_eq!(decode_at(&ram, 4), Some((Instruction::Clear, 2)));
+        assert_eq!(decode_at(&ram, 6), None);
+    }
+
+    #[test]
+    fn jump_offset_follows_the_quirk() {
+        let instruction = decode(0xB312);
+        let vip = QuirkPreset::CosmacVip.quirks();
+        let schip = QuirkPreset::ModernSuperChip.quirks();
+        assert!(!vip.jumping && schip.jumping);
+        assert_eq!(instruction.mnemonic(&vip), "JP V0, 0x312");
+        assert_eq!(instruction.mnemonic(&schip), "JP V3, 0x312");
+        assert_eq!(decode(0x00E0).mnemonic(&schip), "CLS");
+    }
+}
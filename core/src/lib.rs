@@ -0,0 +1,28 @@
+//! CHIP-8, SUPER-CHIP and XO-CHIP interpreter.
+//!
+//! Doesn't depend on bevy, so it can be used by tools and tests
+//! without a window. The emulator's frontend is built on top of it.
+
+pub mod breakpoint;
+pub mod chip8;
+pub mod condition;
+pub mod config;
+pub mod fault;
+mod history;
+pub mod instruction;
+pub mod movie;
+pub mod quirks;
+pub mod rewind;
+mod rng;
+pub mod state;
+mod timer;
+
+pub use breakpoint::{Access, Breakpoint, StopReason, WatchHit, Watchpoint};
+pub use chip8::{Chip8, DisplayPixel, KeyState, StepResult};
+pub use condition::{Condition, ConditionError};
+pub use fault::{Chip8Error, FaultKind};
+pub use instruction::{decode, decode_at, Instruction};
+pub use movie::{Movie, MovieError, MovieStep};
+pub use quirks::{QuirkPreset, Quirks};
+pub use rewind::RewindBuffer;
+pub use state::StateError;
@@ -0,0 +1,324 @@
+//! Input movies, for reproducing a session exactly.
+//!
+//! A movie starts from a freshly loaded ROM and holds the delta time,
+//! run state and keys of every call to `Chip8::step`. With the same
+//! RNG seed, quirks and clock the machine then goes through the same
+//! states when the movie is played back.
+//!
+//! The file starts with `MOVIE_MAGIC` and a little endian `u16` version,
+//! followed by the settings and the steps. Repeated steps are stored once
+//! with a count, as the keys rarely change between steps.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::chip8::KeyState;
+use crate::config::NUM_KEYS;
+use crate::fault::FaultKind;
+use crate::quirks::Quirks;
+use crate::state::{key_state, StateError, StateReader, StateWriter};
+
+pub const MOVIE_MAGIC: &[u8; 4] = b"RCMV";
+pub const MOVIE_VERSION: u16 = 1;
+
+/// Why a movie couldn't be loaded or played.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MovieError {
+    /// The file is not a valid movie.
+    Format(StateError),
+    /// The movie was recorded with another ROM, or no ROM is loaded.
+    WrongRom,
+    /// The ROM couldn't be restarted for the movie.
+    Rom(FaultKind),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::Format(StateError::BadMagic) => write!(f, "Not a movie"),
+            MovieError::Format(StateError::UnsupportedVersion(version)) => {
+                write!(f, "Unsupported movie version {}", version)
+            }
+            MovieError::Format(StateError::Truncated) => write!(f, "Movie is truncated"),
+            MovieError::Format(StateError::Invalid(field)) => {
+                write!(f, "Invalid {} in movie", field)
+            }
+            MovieError::WrongRom => write!(f, "Movie was recorded with another ROM"),
+            MovieError::Rom(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> MovieError {
+        MovieError::Format(err)
+    }
+}
+
+/// One call to `Chip8::step`.
+///
+/// `paused` is set for the steps made by hand while the emulation was paused.
+#[derive(Clone, Copy, PartialEq)]
+pub struct MovieStep {
+    pub delta: Duration,
+    pub paused: bool,
+    pub input: [KeyState; NUM_KEYS],
+}
+
+/// A recorded session. See the module's documentation.
+#[derive(Clone)]
+pub struct Movie {
+    pub rom_id: u64,
+    pub seed: u64,
+    pub clock_hz: u64,
+    pub xo_chip: bool,
+    pub quirks: Quirks,
+    /// Runs of the same step, with their length.
+    runs: Vec<(u32, MovieStep)>,
+}
+
+impl Movie {
+    pub fn new(rom_id: u64, seed: u64, clock_hz: u64, xo_chip: bool, quirks: Quirks) -> Movie {
+        Movie {
+            rom_id,
+            seed,
+            clock_hz,
+            xo_chip,
+            quirks,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Number of recorded steps.
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|&(count, _)| count as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// All the recorded steps, in order.
+    pub fn steps(&self) -> impl Iterator<Item = MovieStep> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|&(count, step)| std::iter::repeat_n(step, count as usize))
+    }
+
+    pub fn push(&mut self, step: MovieStep) {
+        match self.runs.last_mut() {
+            Some((count, last)) if *last == step && *count < u32::MAX => *count += 1,
+            _ => self.runs.push((1, step)),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::with_header(MOVIE_MAGIC, MOVIE_VERSION);
+
+        w.u64(self.rom_id);
+        w.u64(self.seed);
+        w.u64(self.clock_hz);
+        w.bool(self.xo_chip);
+        w.quirks(&self.quirks);
+
+        w.u32(self.runs.len() as u32);
+        for (count, step) in &self.runs {
+            w.u32(*count);
+            w.u64(step.delta.as_nanos() as u64);
+            w.bool(step.paused);
+            for key in step.input {
+                w.u8(key as u8);
+            }
+        }
+
+        w.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut r = StateReader::with_header(data, MOVIE_MAGIC, MOVIE_VERSION)?;
+
+        let rom_id = r.u64()?;
+        let seed = r.u64()?;
+        let clock_hz = r.u64()?;
+        if clock_hz == 0 {
+            return Err(StateError::Invalid("clock").into());
+        }
+        let xo_chip = r.bool()?;
+        let quirks = r.quirks()?;
+        let mut movie = Movie::new(rom_id, seed, clock_hz, xo_chip, quirks);
+
+        let len = r.u32()?;
+        for _ in 0..len {
+            let count = r.u32()?;
+            if count == 0 {
+                return Err(StateError::Invalid("step count").into());
+            }
+            let delta = Duration::from_nanos(r.u64()?);
+            let paused = r.bool()?;
+            let mut input = [KeyState::Released; NUM_KEYS];
+            for key in input.iter_mut() {
+                *key = key_state(r.u8()?)?;
+            }
+            movie.runs.push((
+                count,
+                MovieStep {
+                    delta,
+                    paused,
+                    input,
+                },
+            ));
+        }
+
+        Ok(movie)
+    }
+}
+
+/// Position of the playback in a movie.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct MovieCursor {
+    run: usize,
+    repeat: u32,
+    /// Number of steps played.
+    pub pos: usize,
+}
+
+impl MovieCursor {
+    /// The next step to play, or `None` at the end of the movie.
+    pub fn peek(&self, movie: &Movie) -> Option<MovieStep> {
+        movie.runs.get(self.run).map(|&(_, step)| step)
+    }
+
+    pub fn advance(&mut self, movie: &Movie) {
+        let Some(&(count, _)) = movie.runs.get(self.run) else {
+            return;
+        };
+
+        self.pos += 1;
+        self.repeat += 1;
+        if self.repeat == count {
+            self.run += 1;
+            self.repeat = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::quirks::QuirkPreset;
+
+    fn step(delta_us: u64, paused: bool, key: Option<usize>) -> MovieStep {
+        let mut input = [KeyState::Released; NUM_KEYS];
+        if let Some(key) = key {
+            input[key] = KeyState::Pressed;
+        }
+        MovieStep {
+            delta: Duration::from_micros(delta_us),
+            paused,
+            input,
+        }
+    }
+
+    fn movie() -> Movie {
+        let quirks = QuirkPreset::SuperChip11.quirks();
+        let mut movie = Movie::new(0x1234_5678_9abc_def0, 42, 700, true, quirks);
+        for _ in 0..3 {
+            movie.push(step(1000, false, None));
+        }
+        movie.push(step(1000, false, Some(5)));
+        movie.push(step(0, true, Some(5)));
+        movie.push(step(1000, false, None));
+        movie
+    }
+
+    #[test]
+    fn repeated_steps_are_stored_once() {
+        let movie = movie();
+        assert_eq!(movie.len(), 6);
+        assert_eq!(movie.runs.len(), 4);
+        assert_eq!(movie.runs[0].0, 3);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let movie = movie();
+        let bytes = movie.to_bytes();
+        let loaded = Movie::from_bytes(&bytes).unwrap();
+
+        assert_eq!(loaded.rom_id, movie.rom_id);
+        assert_eq!(loaded.seed, movie.seed);
+        assert_eq!(loaded.clock_hz, movie.clock_hz);
+        assert_eq!(loaded.xo_chip, movie.xo_chip);
+        assert_eq!(loaded.quirks, movie.quirks);
+        assert!(loaded.runs == movie.runs);
+        assert_eq!(loaded.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn from_bytes_errors() {
+        let bytes = movie().to_bytes();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(
+            Movie::from_bytes(&bad_magic).err(),
+            Some(MovieError::Format(StateError::BadMagic))
+        );
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(MOVIE_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Movie::from_bytes(&newer).err(),
+            Some(MovieError::Format(StateError::UnsupportedVersion(
+                MOVIE_VERSION + 1
+            )))
+        );
+
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(MovieError::Format(StateError::Truncated))
+        );
+    }
+
+    #[test]
+    fn playback_repeats_the_recording() {
+        // Waits for key 5, then draws random sprites.
+        let rom = [
+            0xF2, 0x0A, // V2 = key
+            0xC0, 0x3F, // V0 = rand & 0x3F
+            0xC1, 0x1F, // V1 = rand & 0x1F
+            0xD0, 0x15, // draw at V0, V1
+            0x12, 0x02, // jump 0x202
+        ];
+        let delta = Duration::from_nanos(1_000_000_000 / 600);
+
+        let mut chip8 = Chip8::new(600, false);
+        chip8.set_seed(42);
+        chip8.insert_cartridge(&rom).unwrap();
+        assert!(chip8.start_recording());
+        chip8.run();
+        for i in 0..200 {
+            chip8.input[5] = match i {
+                50..=59 => KeyState::Pressed,
+                60 => KeyState::JustReleased,
+                _ => chip8.input[5],
+            };
+            chip8.step(delta).unwrap();
+        }
+        let recorded = chip8.save_state();
+        let movie = Movie::from_bytes(&chip8.stop_recording().unwrap().to_bytes()).unwrap();
+        assert_eq!(movie.len(), 200);
+
+        // Different settings, which the movie replaces with its own.
+        chip8.set_seed(7);
+        chip8.play_movie(movie).unwrap();
+        chip8.run();
+        for _ in 0..200 {
+            chip8.step(delta).unwrap();
+        }
+        assert!(chip8.save_state() == recorded);
+    }
+}
@@ -0,0 +1,133 @@
+/// Behaviour differences between CHIP-8 interpreters.
+///
+/// ROMs are written for a specific interpreter and may misbehave
+/// if the quirks don't match it. `QuirkPreset` has the common ones.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// FX55 and FX65 increment I.
+    pub memory_increment: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shifting: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jumping: bool,
+    /// Drawing waits for the next 60Hz tick, like the COSMAC VIP's vblank.
+    pub display_wait: bool,
+    /// Sprites wrap around the display edges instead of being clipped.
+    pub wrapping: bool,
+    /// Scrolling in low resolution moves by half as much, as on SUPER-CHIP 1.0/1.1.
+    pub legacy_scroll: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        QuirkPreset::ModernSuperChip.quirks()
+    }
+}
+
+/// Quirks of the best known CHIP-8 interpreters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuirkPreset {
+    CosmacVip,
+    Chip48,
+    SuperChip11,
+    ModernSuperChip,
+    XoChip,
+}
+
+impl QuirkPreset {
+    pub const ALL: [QuirkPreset; 5] = [
+        QuirkPreset::CosmacVip,
+        QuirkPreset::Chip48,
+        QuirkPreset::SuperChip11,
+        QuirkPreset::ModernSuperChip,
+        QuirkPreset::XoChip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuirkPreset::CosmacVip => "COSMAC VIP",
+            QuirkPreset::Chip48 => "CHIP-48",
+            QuirkPreset::SuperChip11 => "SUPER-CHIP 1.1",
+            QuirkPreset::ModernSuperChip => "Modern SUPER-CHIP",
+            QuirkPreset::XoChip => "XO-CHIP",
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            QuirkPreset::CosmacVip => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                shifting: false,
+                jumping: false,
+                display_wait: true,
+                wrapping: false,
+                legacy_scroll: false,
+            },
+            // CHIP-48 actually increments I by X instead of X + 1.
+            QuirkPreset::Chip48 => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shifting: true,
+                jumping: true,
+                display_wait: false,
+                wrapping: false,
+                legacy_scroll: false,
+            },
+            QuirkPreset::SuperChip11 => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                shifting: true,
+                jumping: true,
+                display_wait: false,
+                wrapping: false,
+                legacy_scroll: true,
+            },
+            QuirkPreset::ModernSuperChip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                shifting: true,
+                jumping: true,
+                display_wait: false,
+                wrapping: false,
+                legacy_scroll: false,
+            },
+            QuirkPreset::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shifting: false,
+                jumping: false,
+                display_wait: false,
+                wrapping: true,
+                legacy_scroll: false,
+            },
+        }
+    }
+
+    /// Short name of the preset, used on the command line.
+    pub fn id(&self) -> &'static str {
+        match self {
+            QuirkPreset::CosmacVip => "vip",
+            QuirkPreset::Chip48 => "chip48",
+            QuirkPreset::SuperChip11 => "schip11",
+            QuirkPreset::ModernSuperChip => "schip",
+            QuirkPreset::XoChip => "xochip",
+        }
+    }
+
+    /// The preset with the short name `id`, ignoring case.
+    pub fn from_id(id: &str) -> Option<QuirkPreset> {
+        QuirkPreset::ALL
+            .into_iter()
+            .find(|preset| preset.id().eq_ignore_ascii_case(id))
+    }
+
+    /// The preset matching `quirks` exactly, if any.
+    pub fn find(quirks: &Quirks) -> Option<QuirkPreset> {
+        QuirkPreset::ALL
+            .into_iter()
+            .find(|preset| preset.quirks() == *quirks)
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+
+use crate::chip8::Chip8;
+
+/// Ring buffer of save states, used to rewind the emulation.
+///
+/// Once full, pushing a new state drops the oldest one.
+pub struct RewindBuffer {
+    states: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Take a snapshot of the machine.
+    pub fn push(&mut self, chip8: &Chip8) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(chip8.save_state());
+    }
+
+    /// Restore the latest snapshot and drop it.
+    ///
+    /// Returns false if there is nothing to rewind to.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        let Some(state) = self.states.pop_back() else {
+            return false;
+        };
+
+        // The states were saved by this same machine, so they are valid.
+        chip8.load_state(&state).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
@@ -0,0 +1,204 @@
+//! Binary format of the save states.
+//!
+//! A state starts with `STATE_MAGIC` and a little endian `u16` version,
+//! followed by the machine's fields in the order `Chip8::save_state`
+//! writes them. Fields added in later versions go at the end, so
+//! older states can still be loaded with defaults for them.
+//!
+//! Version 2 added the RNG's seed and state.
+
+use std::fmt;
+
+use crate::chip8::KeyState;
+use crate::quirks::Quirks;
+
+pub const STATE_MAGIC: &[u8; 4] = b"RCS8";
+pub const STATE_VERSION: u16 = 2;
+
+/// Why a save state couldn't be loaded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StateError {
+    /// Not a save state.
+    BadMagic,
+    /// Saved by a newer version of the emulator.
+    UnsupportedVersion(u16),
+    /// The data ended early.
+    Truncated,
+    /// A field has a value the emulator can't have.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version {}", version)
+            }
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::Invalid(field) => write!(f, "Invalid {} in save state", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Writes the fields of a state.
+pub(crate) struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter::with_header(STATE_MAGIC, STATE_VERSION)
+    }
+
+    /// Start a file of another format using the same encoding.
+    pub fn with_header(magic: &[u8; 4], version: u16) -> StateWriter {
+        let mut data = Vec::new();
+        data.extend_from_slice(magic);
+        data.extend_from_slice(&version.to_le_bytes());
+        StateWriter { data }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn quirks(&mut self, quirks: &Quirks) {
+        for quirk in [
+            quirks.vf_reset,
+            quirks.memory_increment,
+            quirks.shifting,
+            quirks.jumping,
+            quirks.display_wait,
+            quirks.wrapping,
+            quirks.legacy_scroll,
+        ] {
+            self.bool(quirk);
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads the fields of a state written by `StateWriter`.
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    version: u16,
+}
+
+impl<'a> StateReader<'a> {
+    /// Check the header and start reading the fields after it.
+    pub fn new(data: &'a [u8]) -> Result<StateReader<'a>, StateError> {
+        StateReader::with_header(data, STATE_MAGIC, STATE_VERSION)
+    }
+
+    /// Start reading a file of another format using the same encoding.
+    ///
+    /// `version` is the latest version of the format.
+    pub fn with_header(
+        data: &'a [u8],
+        magic: &[u8; 4],
+        version: u16,
+    ) -> Result<StateReader<'a>, StateError> {
+        if !data.starts_with(magic) {
+            return Err(StateError::BadMagic);
+        }
+
+        let mut reader = StateReader {
+            data,
+            pos: magic.len(),
+            version: 0,
+        };
+        reader.version = reader.u16()?;
+        if reader.version == 0 || reader.version > version {
+            return Err(StateError::UnsupportedVersion(reader.version));
+        }
+
+        Ok(reader)
+    }
+
+    /// Version of the format the data was written with.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(StateError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn quirks(&mut self) -> Result<Quirks, StateError> {
+        Ok(Quirks {
+            vf_reset: self.bool()?,
+            memory_increment: self.bool()?,
+            shifting: self.bool()?,
+            jumping: self.bool()?,
+            display_wait: self.bool()?,
+            wrapping: self.bool()?,
+            legacy_scroll: self.bool()?,
+        })
+    }
+}
+
+/// Decode a key state written as `KeyState as u8`.
+pub(crate) fn key_state(value: u8) -> Result<KeyState, StateError> {
+    match value {
+        0 => Ok(KeyState::Released),
+        1 => Ok(KeyState::Pressed),
+        2 => Ok(KeyState::JustReleased),
+        _ => Err(StateError::Invalid("key state")),
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+/// A repeating timer driven by the time passed to `Chip8::step`.
+///
+/// Works like bevy's repeating `Timer`, so the interpreter
+/// doesn't need to depend on the engine.
+#[derive(Clone)]
+pub struct Timer {
+    duration: Duration,
+    elapsed: Duration,
+}
+
+impl Timer {
+    pub fn new(duration: Duration) -> Timer {
+        Timer {
+            duration,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// True if ticking by `delta` would make the timer go off.
+    ///
+    /// Lets the caller decide what a tick does before committing to it.
+    pub fn would_finish(&self, delta: Duration) -> bool {
+        self.elapsed + delta >= self.duration
+    }
+
+    /// Advance the timer by `delta`.
+    pub fn tick(&mut self, delta: Duration) {
+        self.elapsed += delta;
+        if self.elapsed >= self.duration {
+            let elapsed = self.elapsed.as_nanos() % self.duration.as_nanos();
+            self.elapsed = Duration::from_nanos(elapsed as u64);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use rusty_chip_core::config::PLANE_COUNT;
+use rusty_chip_core::QuirkPreset;
+
+use crate::config::{CHIP8_CPU_MAX_CLOCK_HZ, DEFAULT_CLOCK_HZ, DEFAULT_PALETTE};
+
+pub const USAGE: &str = "\
+Usage: rusty-chip [OPTIONS] [ROM]
+
+Arguments:
+  [ROM]                 ROM to load on start
+
+Options:
+      --clock <HZ>      CPU clock, from 1 to 2000 Hz [default: 600]
+      --quirks <PRESET> Quirk preset: vip, chip48, schip11, schip or xochip
+      --debug           Show the debugging UI, print every executed
+                        instruction and keep the history for stepping back
+      --seed <SEED>     Seed of the random numbers of CXNN
+      --scale <FACTOR>  Scale the display by FACTOR, e.g. 2 or 1.5
+      --palette <COLORS>
+                        Comma separated hex colours for the background and
+                        the planes, e.g. 000000,ffffff,aaaaaa,555555
+      --fullscreen      Start in fullscreen
+      --paused          Don't start running the ROM
+  -h, --help            Print this help";
+
+/// Options given on the command line.
+///
+/// `rom` is loaded on start and starts running, unless `paused` is set.
+pub struct Cli {
+    pub rom: Option<PathBuf>,
+    pub clock_hz: u64,
+    pub quirks: Option<QuirkPreset>,
+    pub debug: bool,
+    pub seed: Option<u64>,
+    pub scale: f32,
+    pub palette: [[u8; 3]; 1 << PLANE_COUNT],
+    pub fullscreen: bool,
+    pub paused: bool,
+    pub help: bool,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Cli {
+            rom: None,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            quirks: None,
+            debug: false,
+            seed: None,
+            scale: 1.0,
+            palette: DEFAULT_PALETTE,
+            fullscreen: false,
+            paused: false,
+            help: false,
+        }
+    }
+}
+
+/// Parse a palette like `000000,ffffff`. Colours not given keep their default.
+fn parse_palette(text: &str) -> Option<[[u8; 3]; 1 << PLANE_COUNT]> {
+    let mut palette = DEFAULT_PALETTE;
+    let colors: Vec<&str> = text.split(',').collect();
+    if colors.len() > palette.len() {
+        return None;
+    }
+
+    for (color, text) in palette.iter_mut().zip(colors) {
+        let text = text.trim().trim_start_matches('#');
+        if text.len() != 6 || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let rgb = u32::from_str_radix(text, 16).ok()?;
+        *color = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+    }
+
+    Some(palette)
+}
+
+impl Cli {
+    /// Parse the arguments, without the program name.
+    ///
+    /// Values can be given as `--clock 700` or `--clock=700`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+
+            let flag = || {
+                if inline_value.is_some() {
+                    return Err(format!("{} doesn't take a value", name));
+                }
+                Ok(true)
+            };
+
+            match name {
+                "-h" | "--help" => cli.help = flag()?,
+                "--debug" => cli.debug = flag()?,
+                "--fullscreen" => cli.fullscreen = flag()?,
+                "--paused" => cli.paused = flag()?,
+                "--clock" => {
+                    let value = value()?;
+                    cli.clock_hz = value
+                        .parse()
+                        .ok()
+                        .filter(|hz| (1..=CHIP8_CPU_MAX_CLOCK_HZ as u64).contains(hz))
+                        .ok_or_else(|| format!("Invalid clock: {}", value))?;
+                }
+                "--quirks" => {
+                    let value = value()?;
+                    cli.quirks = Some(
+                        QuirkPreset::from_id(&value)
+                            .ok_or_else(|| format!("Unknown quirk preset: {}", value))?,
+                    );
+                }
+                "--seed" => {
+                    let value = value()?;
+                    cli.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid seed: {}", value))?,
+                    );
+                }
+                "--scale" => {
+                    let value = value()?;
+                    cli.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale: &f32| scale.is_finite() && *scale > 0.0)
+                        .ok_or_else(|| format!("Invalid scale: {}", value))?;
+                }
+                "--palette" => {
+                    let value = value()?;
+                    cli.palette = parse_palette(&value)
+                        .ok_or_else(|| format!("Invalid palette: {}", value))?;
+                }
+                _ if name.starts_with('-') => return Err(format!("Unknown option: {}", name)),
+                _ if cli.rom.is_none() => cli.rom = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        Ok(cli)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let cli = parse(&[]).unwrap();
+        assert_eq!(cli.rom, None);
+        assert_eq!(cli.clock_hz, DEFAULT_CLOCK_HZ);
+        assert_eq!(cli.quirks, None);
+        assert_eq!(cli.seed, None);
+        assert!(!cli.debug && !cli.fullscreen && !cli.paused && !cli.help);
+    }
+
+    #[test]
+    fn flags() {
+        let cli = parse(&["--debug", "--fullscreen", "--paused", "game.ch8"]).unwrap();
+        assert!(cli.debug && cli.fullscreen && cli.paused);
+        assert_eq!(cli.rom, Some(PathBuf::from("game.ch8")));
+        assert!(parse(&["-h"]).unwrap().help);
+
+        assert_eq!(
+            parse(&["--paused=yes"]).err().unwrap(),
+            "--paused doesn't take a value"
+        );
+        assert!(parse(&["--debug=false"]).is_err());
+
+        // Without dashes it is a ROM.
+        let cli = parse(&["debug"]).unwrap();
+        assert!(!cli.debug);
+        assert_eq!(cli.rom, Some(PathBuf::from("debug")));
+    }
+
+    #[test]
+    fn values() {
+        let cli = parse(&["--clock", "700", "--seed=42", "--quirks", "vip"]).unwrap();
+        assert_eq!(cli.clock_hz, 700);
+        assert_eq!(cli.seed, Some(42));
+        assert_eq!(cli.quirks, Some(QuirkPreset::CosmacVip));
+
+        assert_eq!(parse(&["--seed", "-1"]).err().unwrap(), "Invalid seed: -1");
+        assert_eq!(
+            parse(&["--quirks=chip9"]).err().unwrap(),
+            "Unknown quirk preset: chip9"
+        );
+        assert!(parse(&["--clock", "0"]).is_err());
+        assert!(parse(&["--clock", "2001"]).is_err());
+    }
+
+    #[test]
+    fn missing_values() {
+        for name in ["--clock", "--quirks", "--seed", "--scale", "--palette"] {
+            assert_eq!(
+                parse(&[name]).err().unwrap(),
+                format!("Missing value for {}", name)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_arguments() {
+        assert_eq!(
+            parse(&["--turbo"]).err().unwrap(),
+            "Unknown option: --turbo"
+        );
+        assert_eq!(
+            parse(&["a.ch8", "b.ch8"]).err().unwrap(),
+            "Unexpected argument: b.ch8"
+        );
+    }
+}
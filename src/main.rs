@@ -1,29 +1,77 @@
 use std::time::Duration;
 
+use bevy::audio::AddAudioSource;
 use bevy::time::*;
-use bevy::{prelude::*, window::WindowResizeConstraints};
+use bevy::{
+    prelude::*,
+    window::{WindowMode, WindowResizeConstraints},
+};
 use bevy_egui::EguiPlugin;
 use bevy_pixel_buffer::prelude::*;
-use config::{DELTA_S, DISPLAY_HEIGHT, DISPLAY_WIDTH, HEIGHT, PIXEL_SIZE, WIDTH};
-use resources::chip8::Chip8;
+use cli::Cli;
+use config::{DELTA_S, HEIGHT, PIXEL_SIZE, REWIND_FPS, REWIND_SECONDS, WIDTH};
+use resources::chip8::Chip8Resource;
 use resources::config::ConfigResource;
+use resources::rewind::RewindResource;
 use resources::timer::DrawTimer;
-use systems::{audio, emulator, keyboard, ui};
+use rusty_chip_core::config::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rusty_chip_core::{Chip8, RewindBuffer};
+use systems::audio::PatternAudio;
+use systems::{audio, emulator, keyboard, rewind, ui};
 
+mod cli;
 mod config;
+mod movies;
 mod resources;
+mod rpl_flags;
+mod save_states;
 mod systems;
 
 fn main() -> std::io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let mut debug = false;
-    if args.len() > 1 {
-        if args[1] != "debug" {
-            panic!("Uncrecognised command line argument: {}!", args[1]);
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    let mut chip8 = Chip8::new(cli.clock_hz, cli.debug);
+    if let Some(preset) = cli.quirks {
+        chip8.apply_preset(preset);
+    }
+    if let Some(seed) = cli.seed {
+        chip8.set_seed(seed);
+    }
+    if let Some(path) = &cli.rom {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Couldn't read {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        };
+        if let Err(err) = chip8.insert_cartridge(&data) {
+            eprintln!("Couldn't load {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+        if let Some(flags) = rpl_flags::load(chip8.rom_id()) {
+            chip8.set_rpl_flags(flags);
+        }
+        if !cli.paused {
+            chip8.run();
         }
-        debug = true;
     }
 
+    // Make room for a display bigger than the default.
+    let extra_scale = (cli.scale - 1.0).max(0.0);
+    let width = WIDTH as f32 + extra_scale * (DISPLAY_WIDTH * PIXEL_SIZE) as f32;
+    let height = HEIGHT as f32 + extra_scale * (DISPLAY_HEIGHT * PIXEL_SIZE) as f32;
+
     let pixel_buffer_size = PixelBufferSize {
         size: UVec2::new(DISPLAY_WIDTH * PIXEL_SIZE, DISPLAY_HEIGHT * PIXEL_SIZE),
         pixel_size: UVec2::new(1, 1),
@@ -33,7 +81,12 @@ fn main() -> std::io::Result<()> {
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Chip-8 Rust Emulator".to_string(),
-                resolution: (WIDTH as f32, HEIGHT as f32).into(),
+                resolution: (width, height).into(),
+                mode: if cli.fullscreen {
+                    WindowMode::BorderlessFullscreen
+                } else {
+                    WindowMode::Windowed
+                },
                 resize_constraints: WindowResizeConstraints {
                     min_width: WIDTH as f32,
                     min_height: HEIGHT as f32,
@@ -46,17 +99,30 @@ fn main() -> std::io::Result<()> {
         }))
         .add_plugins(EguiPlugin)
         .add_plugins(PixelBufferPlugins)
-        .insert_resource(Chip8::new(600, debug))
+        .add_audio_source::<PatternAudio>()
+        .insert_resource(Chip8Resource(chip8))
         .insert_resource(ConfigResource {
-            debug_ui: debug,
+            debug_ui: cli.debug,
             show_grid: false,
             trace: false,
             circle_pixels: false,
             reduce_flicker: false,
+            palette: cli.palette,
+            scale: cli.scale,
         })
         .insert_resource(DrawTimer {
             timer: Timer::new(Duration::from_secs_f64(1.0 / 120.0), TimerMode::Repeating),
         })
+        .insert_resource(RewindResource {
+            buffer: RewindBuffer::new(REWIND_SECONDS * REWIND_FPS),
+            timer: Timer::new(
+                Duration::from_secs_f64(1.0 / REWIND_FPS as f64),
+                TimerMode::Repeating,
+            ),
+            rom_id: 0,
+            rewinding: false,
+            resume: false,
+        })
         .insert_resource(Time::<Fixed>::from_seconds(DELTA_S))
         .add_systems(
             Startup,
@@ -71,7 +137,11 @@ fn main() -> std::io::Result<()> {
         .add_systems(FixedUpdate, emulator::emulator_system)
         .add_systems(
             Update,
-            (keyboard::keyboard_system, ui::ui_system.in_set(ui::UiSet)),
+            (
+                keyboard::keyboard_system,
+                rewind::rewind_system,
+                ui::ui_system.in_set(ui::UiSet),
+            ),
         )
         .run();
 
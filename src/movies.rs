@@ -0,0 +1,27 @@
+use std::fs;
+use std::path::Path;
+
+use rusty_chip_core::{Chip8, Movie};
+
+/// Save a recorded movie to `path`.
+pub fn save(movie: &Movie, path: &Path) {
+    if let Err(err) = fs::write(path, movie.to_bytes()) {
+        eprintln!("Couldn't save movie to {}: {}", path.display(), err);
+    }
+}
+
+/// Play back the movie at `path`, restarting the loaded ROM.
+///
+/// The machine is left untouched if the file is not a valid movie
+/// of the loaded ROM.
+pub fn play(chip8: &mut Chip8, path: &Path) {
+    let res = fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| Movie::from_bytes(&data).map_err(|err| err.to_string()))
+        .and_then(|movie| chip8.play_movie(movie).map_err(|err| err.to_string()));
+
+    match res {
+        Ok(()) => chip8.run(),
+        Err(err) => eprintln!("Couldn't play movie from {}: {}", path.display(), err),
+    }
+}
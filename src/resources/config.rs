@@ -1,12 +1,16 @@
 use bevy::prelude::Resource;
 
+use rusty_chip_core::config::PLANE_COUNT;
+
 /// Various configurations for the emulation.
 /// 
-/// `debug` is set byt the command line argument `debug`
+/// `debug_ui` shows the debugging UI. It is set by the command line flag `--debug`.
 /// `show_grid` draws a grid over the pixels. Only enabled when `circle_pixels` is false
 /// `trace` leaves a trace after a pixel is erased. This is one way to reduce flicker.
 /// `circle_pixels` draws CHIP-8's pixels as circles.
 /// `reduce_flicker` tries to reduce the flicker by not updating the screen if a sprite was just erased.
+/// `palette` holds the colours for each combination of XO-CHIP's bitplanes.
+/// `scale` is how much bigger than its default size the display is shown.
 /// 
 /// @note That `reduce_flicker` and `trace` do not work together.
 #[derive(Resource)]
@@ -16,4 +20,6 @@ pub struct ConfigResource {
     pub trace: bool,
     pub circle_pixels: bool,
     pub reduce_flicker: bool,
+    pub palette: [[u8; 3]; 1 << PLANE_COUNT],
+    pub scale: f32,
 }
\ No newline at end of file
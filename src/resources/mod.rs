@@ -1,4 +1,5 @@
 pub mod chip8;
 pub mod config;
+pub mod rewind;
 pub mod timer;
 
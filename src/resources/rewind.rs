@@ -0,0 +1,17 @@
+use bevy::{prelude::Resource, time::Timer};
+use rusty_chip_core::RewindBuffer;
+
+/// Snapshots for rewinding the emulation.
+///
+/// `timer` paces both taking and restoring snapshots.
+/// `rom_id` is the ROM the snapshots belong to.
+/// `rewinding` is set while the rewind key is held.
+/// `resume` is set if the emulation was running when the rewind started.
+#[derive(Resource)]
+pub struct RewindResource {
+    pub buffer: RewindBuffer,
+    pub timer: Timer,
+    pub rom_id: u64,
+    pub rewinding: bool,
+    pub resume: bool,
+}
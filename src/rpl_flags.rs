@@ -0,0 +1,31 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rusty_chip_core::config::RPL_FLAG_COUNT;
+
+use crate::config::RPL_FLAGS_DIR;
+
+/// Path of the file holding the RPL flags of a ROM.
+fn flags_path(rom_id: u64) -> PathBuf {
+    PathBuf::from(RPL_FLAGS_DIR).join(format!("{:016x}.rpl", rom_id))
+}
+
+/// Load the RPL flags saved for the ROM with `rom_id`.
+///
+/// Returns `None` if the ROM never saved any flags.
+pub fn load(rom_id: u64) -> Option<[u8; RPL_FLAG_COUNT]> {
+    let data = fs::read(flags_path(rom_id)).ok()?;
+
+    let mut flags = [0; RPL_FLAG_COUNT];
+    let len = std::cmp::min(data.len(), RPL_FLAG_COUNT);
+    flags[..len].copy_from_slice(&data[..len]);
+
+    Some(flags)
+}
+
+/// Save the RPL flags of the ROM with `rom_id`, so that
+/// high scores and such survive a restart.
+pub fn save(rom_id: u64, flags: &[u8; RPL_FLAG_COUNT]) -> std::io::Result<()> {
+    fs::create_dir_all(RPL_FLAGS_DIR)?;
+    fs::write(flags_path(rom_id), flags)
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusty_chip_core::Chip8;
+
+use crate::config::SAVE_STATES_DIR;
+
+/// Path of the file holding a quick save slot of a ROM.
+fn slot_path(rom_id: u64, slot: usize) -> PathBuf {
+    PathBuf::from(SAVE_STATES_DIR).join(format!("{:016x}.{}.state", rom_id, slot))
+}
+
+/// Save the machine's state to `path`.
+pub fn save(chip8: &Chip8, path: &Path) {
+    if let Err(err) = fs::write(path, chip8.save_state()) {
+        eprintln!("Couldn't save state to {}: {}", path.display(), err);
+    }
+}
+
+/// Load the machine's state from `path`.
+///
+/// The machine is left untouched if the file is not a valid state.
+pub fn load(chip8: &mut Chip8, path: &Path) {
+    let res = fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| chip8.load_state(&data).map_err(|err| err.to_string()));
+
+    if let Err(err) = res {
+        eprintln!("Couldn't load state from {}: {}", path.display(), err);
+    }
+}
+
+/// Save the machine's state to a quick save slot of the loaded ROM.
+pub fn quick_save(chip8: &Chip8, slot: usize) {
+    if let Err(err) = fs::create_dir_all(SAVE_STATES_DIR) {
+        eprintln!("Couldn't create {}: {}", SAVE_STATES_DIR, err);
+        return;
+    }
+
+    save(chip8, &slot_path(chip8.rom_id(), slot));
+}
+
+/// Load the machine's state from a quick save slot of the loaded ROM.
+pub fn quick_load(chip8: &mut Chip8, slot: usize) {
+    let path = slot_path(chip8.rom_id(), slot);
+    load(chip8, &path);
+}
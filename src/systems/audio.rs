@@ -1,11 +1,120 @@
-use bevy::prelude::*;
+use std::sync::{
+    atomic::{AtomicU64, AtomicU8, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use bevy::{
+    audio::{Decodable, Source},
+    prelude::*,
+    reflect::TypePath,
+};
+
+use rusty_chip_core::config::{AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+
+/// Sample rate of the generated XO-CHIP audio.
+const SAMPLE_RATE: u32 = 44100;
 
 /// Resource storing the beeping sound of Chip8
 #[derive(Component)]
 pub struct Beep;
 
+/// Marks the audio source playing XO-CHIP's audio pattern.
+#[derive(Component)]
+pub struct PatternBeep;
+
+/// XO-CHIP's audio pattern and pitch, shared with the audio thread.
+///
+/// The pattern is stored as two 64-bit words so it can be
+/// updated without locking.
+pub struct PatternState {
+    pattern: [AtomicU64; 2],
+    pitch: AtomicU8,
+}
+
+impl PatternState {
+    pub fn update(&self, pattern: &[u8; AUDIO_PATTERN_SIZE], pitch: u8) {
+        for (word, bytes) in self.pattern.iter().zip(pattern.chunks_exact(8)) {
+            word.store(
+                u64::from_be_bytes(bytes.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+        self.pitch.store(pitch, Ordering::Relaxed);
+    }
+}
+
+/// Handle to the audio pattern played by the `PatternBeep` source.
+#[derive(Resource)]
+pub struct PatternAudioState(pub Arc<PatternState>);
+
+/// Audio asset generating XO-CHIP's audio pattern.
+#[derive(Asset, TypePath)]
+pub struct PatternAudio {
+    state: Arc<PatternState>,
+}
+
+/// Plays the 128 1-bit samples of the pattern in a loop.
+pub struct PatternDecoder {
+    state: Arc<PatternState>,
+    position: f32,
+}
+
+impl Iterator for PatternDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pitch = self.state.pitch.load(Ordering::Relaxed) as f32;
+        let rate = 4000.0 * 2f32.powf((pitch - 64.0) / 48.0);
+
+        let bit = self.position as usize;
+        let word = self.state.pattern[bit / 64].load(Ordering::Relaxed);
+        let on = word & (1 << (63 - bit % 64)) != 0;
+
+        self.position = (self.position + rate / SAMPLE_RATE as f32) % 128.0;
+
+        Some(if on { 0.25 } else { -0.25 })
+    }
+}
+
+impl Source for PatternDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for PatternAudio {
+    type DecoderItem = <PatternDecoder as Iterator>::Item;
+    type Decoder = PatternDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        PatternDecoder {
+            state: self.state.clone(),
+            position: 0.0,
+        }
+    }
+}
+
 /// Load CHIP-8's beep sound in bevy.
-pub fn setup_audio_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+///
+/// Also spawns the paused source for XO-CHIP's audio pattern.
+pub fn setup_audio_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut pattern_assets: ResMut<Assets<PatternAudio>>,
+) {
     commands.spawn((
         AudioBundle {
             source: asset_server.load("sounds/c_major.wav"),
@@ -16,4 +125,20 @@ pub fn setup_audio_system(mut commands: Commands, asset_server: Res<AssetServer>
         },
         Beep,
     ));
+
+    let state = Arc::new(PatternState {
+        pattern: Default::default(),
+        pitch: AtomicU8::new(DEFAULT_PITCH),
+    });
+    commands.insert_resource(PatternAudioState(state.clone()));
+    commands.spawn((
+        AudioSourceBundle {
+            source: pattern_assets.add(PatternAudio { state }),
+            settings: PlaybackSettings {
+                paused: true,
+                ..Default::default()
+            },
+        },
+        PatternBeep,
+    ));
 }
@@ -4,15 +4,13 @@ use bevy::prelude::*;
 
 use bevy_pixel_buffer::prelude::*;
 use rayon::prelude::*;
+use rusty_chip_core::{config::DISPLAY_WIDTH, StepResult};
 
 use crate::{
-    config::{DELTA_S, DISPLAY_WIDTH, PIXEL_SIZE},
-    resources::{
-        chip8::{Chip8, StepResult},
-        config::ConfigResource,
-        timer::DrawTimer,
-    },
-    systems::audio::Beep,
+    config::{DELTA_S, PIXEL_SIZE},
+    resources::{chip8::Chip8Resource, config::ConfigResource, timer::DrawTimer},
+    rpl_flags,
+    systems::audio::{Beep, PatternAudioState, PatternBeep},
 };
 
 /// Simple 10x10 matrix representing a circular pixel.
@@ -30,6 +28,27 @@ const CIRCLE_MATRIX: [[u8; PIXEL_SIZE as usize]; PIXEL_SIZE as usize] = [
     [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
 ];
 
+fn to_pixel(color: [u8; 3]) -> Pixel {
+    Pixel {
+        r: color[0],
+        g: color[1],
+        b: color[2],
+        a: 255,
+    }
+}
+
+/// Blend from the `background` to the `foreground` colour
+/// depending on how much the erased pixel has faded.
+fn fade_pixel(background: [u8; 3], foreground: [u8; 3], fade: u8) -> Pixel {
+    let blend = |bg: u8, fg: u8| (bg as i32 + (fg as i32 - bg as i32) * fade as i32 / 255) as u8;
+    Pixel {
+        r: blend(background[0], foreground[0]),
+        g: blend(background[1], foreground[1]),
+        b: blend(background[2], foreground[2]),
+        a: 255,
+    }
+}
+
 /// Step through chip-8's instructions and display the result.
 ///
 /// Drawing happens at 60FPS, unless the `ConfigResouce::reduce_flicker`
@@ -41,24 +60,52 @@ const CIRCLE_MATRIX: [[u8; PIXEL_SIZE as usize]; PIXEL_SIZE as usize] = [
 /// the user clock.
 pub fn emulator_system(
     mut pb: QueryPixelBuffer,
-    mut chip8_resource: ResMut<Chip8>,
+    mut chip8_resource: ResMut<Chip8Resource>,
     mut timer_resource: ResMut<DrawTimer>,
     cfg: ResMut<ConfigResource>,
     beep: Query<&AudioSink, With<Beep>>,
+    pattern_beep: Query<&AudioSink, With<PatternBeep>>,
+    pattern_state: Res<PatternAudioState>,
 ) {
     let delta = Duration::from_secs_f64(DELTA_S);
 
     let mut res = StepResult {
         drawn: false,
         beep: false,
+        stop: None,
     };
 
     if !chip8_resource.paused() {
-        res = chip8_resource.as_mut().step(delta);
+        match chip8_resource.as_mut().step(delta) {
+            Ok(step_res) => res = step_res,
+            Err(err) => eprintln!("CHIP-8 fault: {}", err),
+        }
+    }
+
+    // XO-CHIP ROMs may play their own audio pattern instead of the beep.
+    match chip8_resource.audio_pattern() {
+        Some(pattern) if res.beep => {
+            pattern_state.0.update(pattern, chip8_resource.pitch());
+            if let Ok(sink) = pattern_beep.get_single() {
+                sink.play();
+            }
+        }
+        _ => {
+            if let Ok(sink) = pattern_beep.get_single() {
+                sink.pause();
+            }
+            if res.beep {
+                beep.single().play();
+            }
+        }
     }
 
-    if res.beep {
-        beep.single().play();
+    // A movie starts from cleared flags, which must not replace the saved ones.
+    if chip8_resource.take_rpl_flags_dirty() && !chip8_resource.movie_active() {
+        let rom_id = chip8_resource.rom_id();
+        if let Err(err) = rpl_flags::save(rom_id, chip8_resource.rpl_flags()) {
+            eprintln!("Couldn't save RPL flags: {}", err);
+        }
     }
 
     let force_draw = res.drawn || chip8_resource.is_reset();
@@ -71,34 +118,32 @@ pub fn emulator_system(
     }
 
     let framebuffer = chip8_resource.framebuffer();
+    let width = chip8_resource.display_width();
+
+    // The pixel buffer is sized for the low resolution mode,
+    // so in high resolution mode each CHIP-8 pixel is smaller.
+    let scale = PIXEL_SIZE * DISPLAY_WIDTH / width;
 
     pb.frame().per_pixel_par(|coord, _| {
-        let x = coord.x / PIXEL_SIZE;
-        let y = coord.y / PIXEL_SIZE;
-        let idx: usize = (y * DISPLAY_WIDTH + x) as usize;
+        let x = coord.x / scale;
+        let y = coord.y / scale;
+        let idx: usize = (y * width + x) as usize;
 
-        let is_grid = cfg.show_grid && ((x * PIXEL_SIZE == coord.x) || (y * PIXEL_SIZE == coord.y));
+        let is_grid = cfg.show_grid && ((x * scale == coord.x) || (y * scale == coord.y));
         let outside_circle = cfg.circle_pixels
-            && CIRCLE_MATRIX[(coord.y - y * PIXEL_SIZE) as usize]
-                [(coord.x - x * PIXEL_SIZE) as usize]
+            && CIRCLE_MATRIX[((coord.y - y * scale) * PIXEL_SIZE / scale) as usize]
+                [((coord.x - x * scale) * PIXEL_SIZE / scale) as usize]
                 == 0;
 
-        if framebuffer[idx].0 > 0 && !is_grid && !outside_circle {
-            let c = framebuffer[idx].0;
-            if cfg.trace && c < 255 {
-                Pixel {
-                    r: c,
-                    g: c,
-                    b: c,
-                    a: c,
-                }
-            } else if c == 255 {
-                Pixel::WHITE
-            } else {
-                Pixel::BLACK
-            }
+        let pixel = framebuffer[idx];
+        if is_grid || outside_circle {
+            to_pixel(cfg.palette[0])
+        } else if pixel.planes > 0 {
+            to_pixel(cfg.palette[pixel.planes as usize])
+        } else if cfg.trace && pixel.fade > 0 {
+            fade_pixel(cfg.palette[0], cfg.palette[1], pixel.fade)
         } else {
-            Pixel::BLACK
+            to_pixel(cfg.palette[0])
         }
     });
 
@@ -107,8 +152,8 @@ pub fn emulator_system(
             .framebuffer_mut()
             .par_iter_mut()
             .for_each(|c| {
-                if c.0 > 0 && c.0 < 255 {
-                    c.0 -= std::cmp::min(c.0, 5);
+                if c.fade > 0 {
+                    c.fade -= std::cmp::min(c.fade, 5);
                 }
             });
     }
@@ -1,14 +1,13 @@
 use std::time::Duration;
 
-use crate::{
-    config::{DELTA_S, NUM_KEYS},
-    resources::chip8::{Chip8, KeyState},
-};
+use crate::{config::DELTA_S, resources::chip8::Chip8Resource, save_states};
 use bevy::{
     input::keyboard::KeyboardInput,
     prelude::{EventReader, Input, KeyCode, Res, ResMut},
 };
+use bevy_egui::EguiContexts;
 
+use rusty_chip_core::{config::NUM_KEYS, KeyState};
 use scancode::Scancode;
 
 /// Key mapping from real keyboard to CHIP-8s input.
@@ -67,13 +66,19 @@ const KEY_MAP: [Scancode; NUM_KEYS] = [
 ];
 
 /// Simple input handling system
+///
+/// Keys typed into a text field of the UI are not passed on, only
+/// releases so no CHIP-8 key stays pressed.
 pub fn keyboard_system(
-    mut chip8_res: ResMut<Chip8>,
+    mut chip8_res: ResMut<Chip8Resource>,
     keycodes: Res<Input<KeyCode>>,
     mut key_evr: EventReader<KeyboardInput>,
+    mut egui_ctx: EguiContexts,
 ) {
     use bevy::input::ButtonState;
 
+    let typing = egui_ctx.ctx_mut().wants_keyboard_input();
+
     for ev in key_evr.read() {
         match ev.state {
             ButtonState::Released => {
@@ -85,6 +90,7 @@ pub fn keyboard_system(
                     }
                 }
             }
+            ButtonState::Pressed if typing => {}
             ButtonState::Pressed => {
                 if let Some(sc) = Scancode::new(ev.scan_code as u8) {
                     for (i, key) in KEY_MAP.iter().enumerate() {
@@ -97,8 +103,31 @@ pub fn keyboard_system(
         }
     }
 
+    if typing {
+        return;
+    }
+
+    // F1-F4 quick load the slot with the same number, Shift+F1-F4 quick save it.
+    // Loading is disabled while a movie is recorded or played back.
+    let shift = keycodes.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    for (i, key) in [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4]
+        .into_iter()
+        .enumerate()
+    {
+        if chip8_res.rom_sz() == 0 || !keycodes.just_pressed(key) {
+            continue;
+        }
+
+        if shift {
+            save_states::quick_save(&chip8_res, i + 1);
+        } else if !chip8_res.movie_active() {
+            save_states::quick_load(&mut chip8_res, i + 1);
+        }
+    }
+
     if chip8_res.paused() && keycodes.pressed(KeyCode::Space) {
-        chip8_res.step(Duration::from_secs_f64(DELTA_S));
+        // Faults are kept by `Chip8` and shown by the UI.
+        let _ = chip8_res.step(Duration::from_secs_f64(DELTA_S));
     }
 }
 
@@ -1,5 +1,6 @@
 pub mod audio;
 pub mod emulator;
 pub mod keyboard;
+pub mod rewind;
 pub mod ui;
 
@@ -0,0 +1,59 @@
+use bevy::prelude::{Input, KeyCode, Res, ResMut, Time};
+use bevy_egui::EguiContexts;
+
+use crate::resources::{chip8::Chip8Resource, rewind::RewindResource};
+
+/// Key held to rewind the emulation.
+const REWIND_KEY: KeyCode = KeyCode::Back;
+
+/// Take periodic snapshots of the running emulation and
+/// restore them while the rewind key is held.
+///
+/// The emulation is paused while rewinding. It is resumed once the
+/// key is released, unless it was already paused, so a paused debugging
+/// session can be scrubbed back too.
+///
+/// The key is ignored while typing in a text field of the UI,
+/// where it deletes text.
+pub fn rewind_system(
+    mut chip8_res: ResMut<Chip8Resource>,
+    mut rewind_res: ResMut<RewindResource>,
+    keycodes: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut egui_ctx: EguiContexts,
+) {
+    let rewind = rewind_res.as_mut();
+
+    // Snapshots of another ROM are of no use.
+    if rewind.rom_id != chip8_res.rom_id() {
+        rewind.rom_id = chip8_res.rom_id();
+        rewind.buffer.clear();
+    }
+
+    // Rewinding would break a movie being recorded or played back.
+    let can_rewind = chip8_res.rom_sz() > 0 && !chip8_res.movie_active();
+    let typing = egui_ctx.ctx_mut().wants_keyboard_input();
+    if keycodes.pressed(REWIND_KEY) && can_rewind && !typing {
+        if !rewind.rewinding {
+            rewind.rewinding = true;
+            rewind.resume = !chip8_res.paused();
+            chip8_res.pause();
+        }
+
+        if rewind.timer.tick(time.delta()).just_finished() {
+            rewind.buffer.rewind(&mut chip8_res);
+        }
+        return;
+    }
+
+    if rewind.rewinding {
+        rewind.rewinding = false;
+        if rewind.resume {
+            chip8_res.run();
+        }
+    }
+
+    if !chip8_res.paused() && rewind.timer.tick(time.delta()).just_finished() {
+        rewind.buffer.push(&chip8_res);
+    }
+}
@@ -1,22 +1,27 @@
-use std::io::Read;
-
-use bevy::prelude::{Res, ResMut, SystemSet, Time};
+use bevy::prelude::{Local, Res, ResMut, SystemSet, Time};
 use bevy_egui::{
     egui::{self, load::SizedTexture, Color32, ImageSource, RichText, TextStyle},
     EguiContexts,
 };
 use bevy_pixel_buffer::query::QueryPixelBuffer;
 use rfd::FileDialog;
+use rusty_chip_core::{
+    config::{BIG_FONT_RANGE, FONT_RANGE, REGISTER_COUNT, START_PC},
+    decode, decode_at, Chip8, Condition, Instruction, QuirkPreset, Watchpoint,
+};
 
 use crate::{
-    config::{FONT_RANGE, REGISTER_COUNT, START_PC},
-    resources::{chip8::Chip8, config::ConfigResource},
+    config::QUICK_SAVE_SLOTS,
+    movies,
+    resources::{chip8::Chip8Resource, config::ConfigResource},
+    rpl_flags, save_states,
 };
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub struct UiSet;
 
-fn show_central_panel(egui_ctx: &egui::Context, pb: QueryPixelBuffer) {
+/// Show the display, `scale` times its default size.
+fn show_central_panel(egui_ctx: &egui::Context, pb: QueryPixelBuffer, scale: f32) {
     egui::CentralPanel::default().show(egui_ctx, |ui| {
         ui.centered_and_justified(|ui| {
             // get the egui texture
@@ -25,57 +30,506 @@ fn show_central_panel(egui_ctx: &egui::Context, pb: QueryPixelBuffer) {
             // show the texture as an image
             ui.image(ImageSource::Texture(SizedTexture {
                 id: texture.id,
-                size: texture.size,
+                size: texture.size * scale,
             }));
         });
     });
 }
 
+/// Show the fault that paused the emulation, if any.
+fn show_fault_window(egui_ctx: &egui::Context, chip8: &mut Chip8) {
+    let Some(fault) = chip8.fault().copied() else {
+        return;
+    };
+
+    egui::Window::new("Fault")
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx, |ui| {
+            ui.label(
+                RichText::new(fault.kind.to_string())
+                    .color(Color32::LIGHT_RED)
+                    .strong(),
+            );
+            ui.label(
+                RichText::new(format!("PC:     0x{:04x}", fault.pc))
+                    .text_style(TextStyle::Monospace),
+            );
+            ui.label(
+                RichText::new(format!("Opcode: 0x{:04x}", fault.opcode))
+                    .text_style(TextStyle::Monospace),
+            );
+            ui.label("The emulation was paused.");
+
+            ui.horizontal(|ui| {
+                if ui.button("Dismiss").clicked() {
+                    chip8.clear_fault();
+                }
+                if ui
+                    .add_enabled(!chip8.movie_active(), egui::Button::new("Reset"))
+                    .clicked()
+                {
+                    chip8.reset();
+                }
+            });
+        });
+}
+
+/// Show a message for the user, e.g. why a file couldn't be opened,
+/// until it is dismissed.
+fn show_message_window(egui_ctx: &egui::Context, message: &mut Option<String>) {
+    let Some(text) = message.as_ref() else {
+        return;
+    };
+
+    let mut dismissed = false;
+    egui::Window::new("Error")
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx, |ui| {
+            ui.label(RichText::new(text).color(Color32::LIGHT_RED));
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        *message = None;
+    }
+}
+
+/// Number of instructions shown before and after the PC.
+const DISASSEMBLY_BEFORE: usize = 16;
+const DISASSEMBLY_AFTER: usize = 48;
+
+/// Show the instructions around the PC.
+///
+/// Clicking an address toggles a breakpoint on it.
+/// Right clicking an instruction allows running to it.
+/// If `follow_pc` is true the current instruction is scrolled into view.
+fn show_disassembly(ui: &mut egui::Ui, chip8: &mut Chip8, follow_pc: bool) {
+    let pc = chip8.pc() as usize;
+    let ram = chip8.ram();
+
+    // Code before the PC can't be told apart from data, so it is
+    // assumed to be made of 2 byte instructions.
+    let mut addr = pc - 2 * DISASSEMBLY_BEFORE.min(pc / 2);
+    let mut toggled = None;
+    let mut run_to = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for _ in 0..DISASSEMBLY_BEFORE + DISASSEMBLY_AFTER {
+                let Some((instruction, len)) = decode_at(ram, addr) else {
+                    break;
+                };
+
+                let bytes = &ram[addr..ram.len().min(addr + len)];
+                let raw: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                let mnemonic = match instruction {
+                    Instruction::LongLoadIndex if bytes.len() == 4 => {
+                        format!("LD I, 0x{:04X}", u16::from_be_bytes([bytes[2], bytes[3]]))
+                    }
+                    _ => instruction.mnemonic(&chip8.quirks),
+                };
+
+                let (marker, color) = match chip8.breakpoints().get(&(addr as u16)) {
+                    Some(breakpoint) if breakpoint.enabled => ("●", Color32::LIGHT_RED),
+                    Some(_) => ("○", Color32::GRAY),
+                    None => (" ", Color32::WHITE),
+                };
+                ui.horizontal(|ui| {
+                    let address = egui::Label::new(
+                        RichText::new(format!("{} 0x{:04x}", marker, addr))
+                            .text_style(TextStyle::Monospace)
+                            .color(color),
+                    )
+                    .sense(egui::Sense::click());
+                    if ui.add(address).clicked() {
+                        toggled = Some(addr as u16);
+                    }
+
+                    let mut line = RichText::new(format!("{:<8} {}", raw, mnemonic))
+                        .text_style(TextStyle::Monospace);
+                    if addr == pc {
+                        line = line
+                            .color(Color32::BLACK)
+                            .background_color(Color32::LIGHT_GREEN);
+                    }
+                    let line = ui.label(line).context_menu(|ui| {
+                        if ui.button("Run to cursor").clicked() {
+                            run_to = Some(addr as u16);
+                            ui.close_menu();
+                        }
+                    });
+                    if addr == pc && follow_pc {
+                        line.scroll_to_me(Some(egui::Align::Center));
+                    }
+                });
+
+                addr += if addr < pc { 2 } else { len };
+            }
+        });
+
+    if let Some(addr) = toggled {
+        chip8.toggle_breakpoint(addr);
+    }
+    if let Some(addr) = run_to {
+        chip8.run_to(addr);
+    }
+}
+
+/// Breakpoint being entered in the debug UI.
+#[derive(Default)]
+pub struct BreakpointForm {
+    addr: String,
+    condition: String,
+    error: Option<String>,
+}
+
+/// Show the list of breakpoints.
+///
+/// New breakpoints are added by typing their address in hex and
+/// optionally a condition, e.g. `V3 == 0x10 && I > 0x300`.
+/// Adding a breakpoint at an existing address replaces its condition.
+fn show_breakpoints(ui: &mut egui::Ui, chip8: &mut Chip8, form: &mut BreakpointForm) {
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut form.addr)
+                .hint_text("Address (hex)")
+                .font(TextStyle::Monospace)
+                .desired_width(100.0),
+        );
+
+        let addr = parse_addr(&form.addr).and_then(|addr| u16::try_from(addr).ok());
+        if ui
+            .add_enabled(addr.is_some(), egui::Button::new("Add"))
+            .clicked()
+        {
+            if let Some(addr) = addr {
+                let condition = if form.condition.trim().is_empty() {
+                    Ok(None)
+                } else {
+                    Condition::parse(&form.condition).map(Some)
+                };
+
+                match condition {
+                    Ok(condition) => {
+                        chip8.add_breakpoint(addr);
+                        chip8.set_breakpoint_condition(addr, condition);
+                        *form = BreakpointForm::default();
+                    }
+                    Err(err) => form.error = Some(err.to_string()),
+                }
+            }
+        }
+    });
+
+    ui.add(
+        egui::TextEdit::singleline(&mut form.condition)
+            .hint_text("Condition (optional)")
+            .font(TextStyle::Monospace),
+    );
+    if let Some(err) = &form.error {
+        ui.label(RichText::new(err).color(Color32::LIGHT_RED));
+    }
+
+    let mut removed = None;
+    let mut toggled = None;
+    for (&addr, breakpoint) in chip8.breakpoints() {
+        ui.horizontal(|ui| {
+            let mut enabled = breakpoint.enabled;
+            if ui.checkbox(&mut enabled, "").changed() {
+                toggled = Some((addr, enabled));
+            }
+            let text = match &breakpoint.condition {
+                Some(condition) => format!("0x{:04x} if {}", addr, condition),
+                None => format!("0x{:04x}", addr),
+            };
+            ui.label(RichText::new(text).text_style(TextStyle::Monospace));
+            if ui.button("Remove").clicked() {
+                removed = Some(addr);
+            }
+        });
+    }
+
+    if let Some((addr, enabled)) = toggled {
+        chip8.set_breakpoint_enabled(addr, enabled);
+    }
+    if let Some(addr) = removed {
+        chip8.remove_breakpoint(addr);
+    }
+}
+
+/// Watchpoint being entered in the debug UI.
+#[derive(Default)]
+pub struct WatchpointForm {
+    start: String,
+    end: String,
+    read: bool,
+    write: bool,
+}
+
+/// Parse an address typed in hex, with or without the `0x` prefix.
+fn parse_addr(text: &str) -> Option<usize> {
+    usize::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// Show the list of watchpoints.
+///
+/// A watchpoint covers the addresses from start to end. The end
+/// can be left empty to watch a single address.
+fn show_watchpoints(ui: &mut egui::Ui, chip8: &mut Chip8, form: &mut WatchpointForm) {
+    ui.horizontal(|ui| {
+        for (text, hint) in [(&mut form.start, "Start (hex)"), (&mut form.end, "End")] {
+            ui.add(
+                egui::TextEdit::singleline(text)
+                    .hint_text(hint)
+                    .font(TextStyle::Monospace)
+                    .desired_width(70.0),
+            );
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut form.read, "Read");
+        ui.checkbox(&mut form.write, "Write");
+
+        let start = parse_addr(&form.start);
+        let end = if form.end.trim().is_empty() {
+            start
+        } else {
+            parse_addr(&form.end)
+        };
+        let valid = matches!((start, end), (Some(start), Some(end)) if start <= end)
+            && (form.read || form.write);
+        if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+            if let (Some(start), Some(end)) = (start, end) {
+                chip8.add_watchpoint(Watchpoint::new(start..=end, form.read, form.write));
+                form.start.clear();
+                form.end.clear();
+            }
+        }
+    });
+
+    let mut removed = None;
+    let mut toggled = None;
+    for (i, watchpoint) in chip8.watchpoints().iter().enumerate() {
+        ui.horizontal(|ui| {
+            let mut enabled = watchpoint.enabled;
+            if ui.checkbox(&mut enabled, "").changed() {
+                toggled = Some((i, enabled));
+            }
+            ui.label(
+                RichText::new(format!(
+                    "0x{:04x}-0x{:04x} {}{}",
+                    watchpoint.range.start(),
+                    watchpoint.range.end(),
+                    if watchpoint.read { "R" } else { "" },
+                    if watchpoint.write { "W" } else { "" },
+                ))
+                .text_style(TextStyle::Monospace),
+            );
+            if ui.button("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+
+    if let Some((i, enabled)) = toggled {
+        chip8.set_watchpoint_enabled(i, enabled);
+    }
+    if let Some(i) = removed {
+        chip8.remove_watchpoint(i);
+    }
+}
+
+/// Show the seed of the RNG used by CXNN, which can be changed
+/// to a decimal number typed in `text`.
+///
+/// The same seed makes a ROM behave the same on every run.
+fn show_seed(ui: &mut egui::Ui, chip8: &mut Chip8, text: &mut String) {
+    ui.label(RichText::new(format!("RNG seed: {}", chip8.seed())).text_style(TextStyle::Monospace));
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(text)
+                .hint_text("New seed")
+                .font(TextStyle::Monospace)
+                .desired_width(160.0),
+        );
+
+        let seed = text.trim().parse::<u64>().ok();
+        if ui
+            .add_enabled(seed.is_some(), egui::Button::new("Set"))
+            .clicked()
+        {
+            if let Some(seed) = seed {
+                chip8.set_seed(seed);
+                text.clear();
+            }
+        }
+    });
+}
+
 /// Draw the emulator's UI.
 ///
 /// Depending on `ConfigResource::debug_ui` we will draw only the
 /// user configurations and the display or the debugging UI also.
 pub fn ui_system(
     mut egui_ctx: EguiContexts,
-    mut chip8_res: ResMut<Chip8>,
+    mut chip8_res: ResMut<Chip8Resource>,
     mut cfg: ResMut<ConfigResource>,
     pb: QueryPixelBuffer,
     time: Res<Time>,
+    mut last_pc: Local<Option<u16>>,
+    mut new_breakpoint: Local<BreakpointForm>,
+    mut new_watchpoint: Local<WatchpointForm>,
+    mut new_seed: Local<String>,
+    mut message: Local<Option<String>>,
 ) {
     let ctx = egui_ctx.ctx_mut();
 
     egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
         egui::menu::bar(ui, |ui| {
             egui::menu::menu_button(ui, "File", |ui| {
-                if ui.button("Open").clicked() {
+                // Anything but stepping the machine would break a movie.
+                let movie_active = chip8_res.movie_active();
+                if ui
+                    .add_enabled(!movie_active, egui::Button::new("Open"))
+                    .clicked()
+                {
                     let file = FileDialog::new()
-                        .add_filter("", &["ch8"])
+                        .add_filter("", &["ch8", "sc8", "xo8"])
                         .set_directory("test/")
                         .pick_file();
 
-                    if file.is_none() {
-                        ui.close_menu();
-                        return;
+                    if let Some(path) = file {
+                        let res = std::fs::read(&path)
+                            .map_err(|err| err.to_string())
+                            .and_then(|data| {
+                                chip8_res
+                                    .insert_cartridge(&data)
+                                    .map_err(|err| err.to_string())
+                            });
+                        match res {
+                            Ok(()) => {
+                                if let Some(flags) = rpl_flags::load(chip8_res.rom_id()) {
+                                    chip8_res.set_rpl_flags(flags);
+                                }
+                            }
+                            Err(err) => {
+                                *message =
+                                    Some(format!("Couldn't open {}: {}", path.display(), err))
+                            }
+                        }
                     }
 
-                    let f =
-                        std::fs::File::open(file.unwrap().as_path()).expect("Invalid filename!");
-                    let len = f.metadata().unwrap().len();
+                    ui.close_menu();
+                }
 
-                    let mut data = vec![0u8; len as usize];
+                ui.separator();
 
-                    let mut file = std::io::BufReader::new(f);
-                    file.read_exact(data.as_mut_slice())
-                        .expect("Couldn't read file!");
+                let has_rom = chip8_res.rom_sz() > 0;
+                if ui
+                    .add_enabled(has_rom, egui::Button::new("Save State"))
+                    .clicked()
+                {
+                    let file = FileDialog::new()
+                        .add_filter("", &["state"])
+                        .set_file_name("save.state")
+                        .save_file();
+                    if let Some(path) = file {
+                        save_states::save(&chip8_res, &path);
+                    }
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(!movie_active, egui::Button::new("Load State"))
+                    .clicked()
+                {
+                    let file = FileDialog::new().add_filter("", &["state"]).pick_file();
+                    if let Some(path) = file {
+                        save_states::load(&mut chip8_res, &path);
+                    }
+                    ui.close_menu();
+                }
 
-                    chip8_res.insert_cartridge(&data);
+                ui.add_enabled_ui(has_rom, |ui| {
+                    ui.menu_button("Quick Save", |ui| {
+                        for slot in 1..=QUICK_SAVE_SLOTS {
+                            if ui
+                                .button(format!("Slot {} (Shift+F{})", slot, slot))
+                                .clicked()
+                            {
+                                save_states::quick_save(&chip8_res, slot);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.add_enabled_ui(!movie_active, |ui| {
+                        ui.menu_button("Quick Load", |ui| {
+                            for slot in 1..=QUICK_SAVE_SLOTS {
+                                if ui.button(format!("Slot {} (F{})", slot, slot)).clicked() {
+                                    save_states::quick_load(&mut chip8_res, slot);
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                });
 
-                    ui.close_menu();
+                ui.separator();
+
+                if chip8_res.recording() {
+                    if ui.button("Stop Recording").clicked() {
+                        let movie = chip8_res.stop_recording();
+                        let file = FileDialog::new()
+                            .add_filter("", &["movie"])
+                            .set_file_name("session.movie")
+                            .save_file();
+                        if let (Some(movie), Some(path)) = (movie, file) {
+                            movies::save(&movie, &path);
+                        }
+                        ui.close_menu();
+                    }
+                } else if movie_active {
+                    if ui.button("Stop Playback").clicked() {
+                        chip8_res.stop_movie();
+                        ui.close_menu();
+                    }
+                } else {
+                    if ui
+                        .add_enabled(has_rom, egui::Button::new("Record Movie"))
+                        .on_hover_text("Restarts the ROM and records the session.")
+                        .clicked()
+                    {
+                        if chip8_res.start_recording() {
+                            chip8_res.run();
+                        } else {
+                            *message =
+                                Some("Couldn't restart the ROM to record a movie".to_string());
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(has_rom, egui::Button::new("Play Movie"))
+                        .clicked()
+                    {
+                        let file = FileDialog::new().add_filter("", &["movie"]).pick_file();
+                        if let Some(path) = file {
+                            movies::play(&mut chip8_res, &path);
+                        }
+                        ui.close_menu();
+                    }
                 }
             });
         });
     });
 
+    show_fault_window(ctx, &mut chip8_res);
+    show_message_window(ctx, &mut message);
+
     egui::SidePanel::left("left_panel").show(ctx, |ui| {
         ui.heading("Simulation Control");
         ui.separator();
@@ -93,20 +547,103 @@ pub fn ui_system(
                 chip8_res.run();
             }
             if cfg.debug_ui && chip8_res.paused() && ui.button("Step").clicked() {
-                chip8_res.step(time.delta());
+                // Faults are kept by `Chip8` and shown in the fault panel.
+                let _ = chip8_res.step(time.delta());
+            }
+            if cfg.debug_ui && chip8_res.paused() {
+                if ui
+                    .add_enabled(chip8_res.can_step_back(), egui::Button::new("Step back"))
+                    .clicked()
+                {
+                    chip8_res.step_back();
+                }
+                if ui.button("Step over").clicked() {
+                    chip8_res.step_over();
+                }
+                if ui
+                    .add_enabled(chip8_res.sp() > 0, egui::Button::new("Step out"))
+                    .clicked()
+                {
+                    chip8_res.step_out();
+                }
             }
-            if ui.button("Reset").clicked() {
+            if ui
+                .add_enabled(!chip8_res.movie_active(), egui::Button::new("Reset"))
+                .clicked()
+            {
                 chip8_res.reset();
             }
         });
 
+        if chip8_res.recording() {
+            ui.label(RichText::new("Recording movie").color(Color32::LIGHT_RED));
+        }
+        if let Some((pos, len)) = chip8_res.playback_progress() {
+            ui.label(format!("Playing movie: step {} of {}", pos, len));
+        }
+
         ui.separator();
 
-        ui.checkbox(&mut chip8_res.super_chip, "SuperChip/Chip-48 behaviour")
-            .on_hover_ui(|ui| {
-                ui.label("Changes the behaviour of some instructions.");
+        // Movies are played back with the settings they were recorded with.
+        let settings_enabled = !chip8_res.movie_active();
+        ui.add_enabled_ui(settings_enabled, |ui| {
+            let preset = QuirkPreset::find(&chip8_res.quirks);
+            egui::ComboBox::from_label("Quirks")
+                .selected_text(preset.map_or("Custom", |preset| preset.name()))
+                .show_ui(ui, |ui| {
+                    for p in QuirkPreset::ALL {
+                        if ui.selectable_label(preset == Some(p), p.name()).clicked() {
+                            chip8_res.apply_preset(p);
+                        }
+                    }
+                })
+                .response
+                .on_hover_ui(|ui| {
+                    ui.label("Changes the behaviour of some instructions.");
+                });
+
+            ui.collapsing("Quirk toggles", |ui| {
+                let quirks = &mut chip8_res.quirks;
+                ui.checkbox(&mut quirks.vf_reset, "VF reset")
+                    .on_hover_ui(|ui| {
+                        ui.label("8XY1, 8XY2 and 8XY3 reset VF to 0.");
+                    });
+                ui.checkbox(&mut quirks.memory_increment, "Memory increment")
+                    .on_hover_ui(|ui| {
+                        ui.label("FX55 and FX65 increment I.");
+                    });
+                ui.checkbox(&mut quirks.shifting, "Shifting")
+                    .on_hover_ui(|ui| {
+                        ui.label("8XY6 and 8XYE shift VX in place, ignoring VY.");
+                    });
+                ui.checkbox(&mut quirks.jumping, "Jumping")
+                    .on_hover_ui(|ui| {
+                        ui.label("BNNN jumps to XNN + VX instead of NNN + V0.");
+                    });
+                ui.checkbox(&mut quirks.display_wait, "Display wait")
+                    .on_hover_ui(|ui| {
+                        ui.label("Drawing waits for the next 60Hz tick.");
+                    });
+                ui.checkbox(&mut quirks.wrapping, "Wrapping")
+                    .on_hover_ui(|ui| {
+                        ui.label("Sprites wrap around the screen edges instead of being clipped.");
+                    });
+                ui.checkbox(&mut quirks.legacy_scroll, "Legacy scrolling")
+                    .on_hover_ui(|ui| {
+                        ui.label("SuperChip 1.0/1.1 scrolls only half as far in low resolution.");
+                    });
             });
 
+            let mut xo_chip = chip8_res.xo_chip();
+            ui.checkbox(&mut xo_chip, "XO-CHIP extensions")
+                .on_hover_ui(|ui| {
+                    ui.label("Enables XO-CHIP's instructions and 64KB of RAM.");
+                });
+            if xo_chip != chip8_res.xo_chip() {
+                chip8_res.set_xo_chip(xo_chip);
+            }
+        });
+
         ui.checkbox(&mut cfg.circle_pixels, "Circular pixels");
 
         if !cfg.circle_pixels {
@@ -129,10 +666,27 @@ pub fn ui_system(
             chip8_res.set_reduce_flicker(cfg.reduce_flicker);
         }
 
+        ui.collapsing("Palette", |ui| {
+            const NAMES: [&str; 4] = ["Background", "Plane 1", "Plane 2", "Both planes"];
+            for (color, name) in cfg.palette.iter_mut().zip(NAMES) {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgb(color);
+                    ui.label(name);
+                });
+            }
+        });
+
         let mut clock_hz = chip8_res.clock_hz;
-        ui.add(egui::Slider::new(&mut clock_hz, 1..=2000).text("Cpu clock in Hz"));
+        ui.add_enabled(
+            settings_enabled,
+            egui::Slider::new(&mut clock_hz, 1..=2000).text("Cpu clock in Hz"),
+        );
         chip8_res.change_clock(clock_hz);
 
+        ui.add_enabled_ui(settings_enabled, |ui| {
+            show_seed(ui, &mut chip8_res, &mut new_seed);
+        });
+
         if !cfg.debug_ui {
             return;
         }
@@ -151,17 +705,47 @@ pub fn ui_system(
             RichText::new(format!("PC: {} (0x{:03x})", pc, pc)).text_style(TextStyle::Monospace),
         );
         ui.label(RichText::new(format!("SP: {}", chip8_res.sp())).text_style(TextStyle::Monospace));
-
-        let instr_bytes: &[u8] = &chip8_res.ram()[pc..=pc + 1];
-        let instr: u16 = ((instr_bytes[0] as u16) << 8) | instr_bytes[1] as u16;
         ui.label(
-            RichText::new(format!("Next instruction: 0x{:04x}", instr))
-                .text_style(TextStyle::Monospace),
+            RichText::new(format!(
+                "Display: {}x{}{}",
+                chip8_res.display_width(),
+                chip8_res.display_height(),
+                if chip8_res.hires() { " (hires)" } else { "" }
+            ))
+            .text_style(TextStyle::Monospace),
         );
+
+        if let Some(instr_bytes) = chip8_res.ram().get(pc..=pc + 1) {
+            let instr: u16 = ((instr_bytes[0] as u16) << 8) | instr_bytes[1] as u16;
+            ui.label(
+                RichText::new(format!(
+                    "Next instruction: 0x{:04x} {}",
+                    instr,
+                    decode(instr).mnemonic(&chip8_res.quirks)
+                ))
+                .text_style(TextStyle::Monospace),
+            );
+        }
+
+        if let Some(stop) = chip8_res.stop_reason() {
+            ui.label(
+                RichText::new(format!("Stopped: {}", stop))
+                    .text_style(TextStyle::Monospace)
+                    .color(Color32::LIGHT_RED),
+            );
+        }
+
+        ui.collapsing("Breakpoints", |ui| {
+            show_breakpoints(ui, &mut chip8_res, &mut new_breakpoint);
+        });
+
+        ui.collapsing("Watchpoints", |ui| {
+            show_watchpoints(ui, &mut chip8_res, &mut new_watchpoint);
+        });
     });
 
     if !cfg.debug_ui {
-        show_central_panel(ctx, pb);
+        show_central_panel(ctx, pb, cfg.scale);
         return;
     }
 
@@ -193,6 +777,16 @@ pub fn ui_system(
         }
     });
 
+    egui::SidePanel::right("disassembly_panel").show(ctx, |ui| {
+        ui.heading("Disassembly");
+
+        ui.separator();
+
+        let pc = chip8_res.pc();
+        show_disassembly(ui, &mut chip8_res, *last_pc != Some(pc));
+        *last_pc = Some(pc);
+    });
+
     egui::TopBottomPanel::bottom("bottom_panel")
         .resizable(true)
         .show(ctx, |ui| {
@@ -200,32 +794,36 @@ pub fn ui_system(
 
             ui.separator();
 
-            let scroll_area = egui::ScrollArea::vertical().auto_shrink([false, true]);
+            const ROW_LEN: usize = 16;
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("        ").text_style(TextStyle::Monospace));
+                for i in 0..ROW_LEN {
+                    ui.label(
+                        RichText::new(format!(
+                            "{:02x}{}",
+                            i,
+                            if i == ROW_LEN - 1 { "" } else { " " }
+                        ))
+                        .text_style(TextStyle::Monospace),
+                    );
+                }
+            });
 
-            scroll_area.show(ui, |ui| {
-                const ROW_LEN: usize = 16;
-                ui.horizontal(|ui| {
-                    ui.label(RichText::new("        ").text_style(TextStyle::Monospace));
-                    for i in 0..ROW_LEN {
-                        ui.label(
-                            RichText::new(format!(
-                                "{:02x}{}",
-                                i,
-                                if i == ROW_LEN - 1 { "" } else { " " }
-                            ))
-                            .text_style(TextStyle::Monospace),
-                        );
-                    }
-                });
+            let scroll_area = egui::ScrollArea::vertical().auto_shrink([false, true]);
 
-                let ram = &chip8_res.ram();
-                let mut i = 0;
-                while i < ram.len() {
+            // XO-CHIP has 64KB of RAM, so only draw the visible rows.
+            let ram = &chip8_res.ram();
+            let row_height = ui.text_style_height(&TextStyle::Monospace);
+            scroll_area.show_rows(ui, row_height, ram.len() / ROW_LEN, |ui, rows| {
+                for row in rows {
+                    let i = row * ROW_LEN;
                     ui.horizontal(|ui| {
                         const FONT_ADDR: usize = FONT_RANGE.start;
+                        const BIG_FONT_ADDR: usize = BIG_FONT_RANGE.start;
                         let (color, tail_label) = match i {
                             START_PC => (Color32::LIGHT_RED, " (ROM)"),
                             FONT_ADDR => (Color32::LIGHT_BLUE, " (FONT)"),
+                            BIG_FONT_ADDR => (Color32::LIGHT_BLUE, " (BIG FONT)"),
                             _ => (Color32::WHITE, ""),
                         };
                         ui.label(
@@ -245,10 +843,9 @@ pub fn ui_system(
                             );
                         }
                     });
-                    i += 16;
                 }
             });
         });
 
-    show_central_panel(ctx, pb);
+    show_central_panel(ctx, pb, cfg.scale);
 }